@@ -24,5 +24,8 @@ fn main() -> Result<(), Report> {
         .map(ShortScoreCard::from)
         .collect::<Vec<ShortScoreCard>>();
     fs::create_dir_all(OUTPUT_DIR)?;
-    Ok(ShortScoreCard::to_csv(SHORTCODES_CSV, &short_scorecards)?)
+    Ok(ShortScoreCard::write_csv(
+        SHORTCODES_CSV,
+        &short_scorecards,
+    )?)
 }
@@ -1,9 +1,10 @@
 use aws_config::BehaviorVersion;
-use aws_sdk_s3::primitives::{ByteStream, SdkBody};
+use aws_sdk_s3::primitives::{ByteStream, ByteStreamError, SdkBody};
 use std::path::{Path, PathBuf};
 use time::{macros::format_description, OffsetDateTime};
 
-use crate::Error;
+use super::AWSError;
+use crate::{versioning::Calver, Error};
 
 /// Return the calver notation YY.0M for the UTC now date.
 fn calver_utc_now() -> String {
@@ -62,20 +63,16 @@ where
 /// Paths are ignored if:
 ///   - they are not valid unicode
 ///   - they do not end with a number
+///   - they do not start with a valid YY.0M version
 pub fn calver_next(dirs: &[PathBuf]) -> u32 {
     let with_micro = dirs
         .iter()
         .filter_map(|d| d.file_name())
         .filter_map(|d| d.to_str())
         .filter(|d| d.chars().filter(|c| *c == '.').count() == 2)
-        .filter_map(|d| {
-            d.split('.')
-                .collect::<Vec<&str>>()
-                .last()
-                .unwrap()
-                .parse::<u32>()
-                .ok()
-        })
+        .filter_map(|d| d.rsplit_once('.'))
+        .filter(|(version, _)| Calver::try_from_ubuntu(version).is_ok())
+        .filter_map(|(_, micro)| micro.parse::<u32>().ok())
         .collect::<Vec<u32>>();
 
     // If there is no directory with a micro part, create the first one.
@@ -86,6 +83,24 @@ pub fn calver_next(dirs: &[PathBuf]) -> u32 {
     }
 }
 
+/// Options used to create the S3 directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3DirectoryOptions {
+    /// Prefix prepended to the keys, e.g. `results`.
+    pub key_prefix: Option<String>,
+    /// Create the directory placeholder object with a trailing slash.
+    pub trailing_slash: bool,
+}
+
+impl Default for S3DirectoryOptions {
+    fn default() -> Self {
+        Self {
+            key_prefix: None,
+            trailing_slash: true,
+        }
+    }
+}
+
 /// Create S3 directories in a sepecific bucket, following the PFB convention.
 pub async fn create_calver_s3_directories(
     bucket_name: &str,
@@ -93,14 +108,35 @@ pub async fn create_calver_s3_directories(
     city: &str,
     region: Option<&str>,
 ) -> Result<PathBuf, crate::Error> {
-    // Get the base path.
-    let s3_dir = calver_base::<PathBuf>(country, city, region, None, None);
-    let mut s3_dir_str = s3_dir.to_str().unwrap().to_string();
-
     // Configure the S3 client.
     let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
     let client = aws_sdk_s3::Client::new(&config);
 
+    create_calver_s3_directories_with(
+        &client,
+        bucket_name,
+        country,
+        city,
+        region,
+        &S3DirectoryOptions::default(),
+    )
+    .await
+}
+
+/// Create S3 directories in a sepecific bucket, following the PFB convention,
+/// using a specific client and specific options.
+pub async fn create_calver_s3_directories_with(
+    client: &aws_sdk_s3::Client,
+    bucket_name: &str,
+    country: &str,
+    city: &str,
+    region: Option<&str>,
+    options: &S3DirectoryOptions,
+) -> Result<PathBuf, crate::Error> {
+    // Get the base path.
+    let s3_dir = calver_base(country, city, region, None, options.key_prefix.as_ref());
+    let mut s3_dir_str = s3_dir.to_str().unwrap().to_string();
+
     // List the existing directory matching the base path.
     let mut response = client
         .list_objects_v2()
@@ -116,7 +152,7 @@ pub async fn create_calver_s3_directories(
             Ok(output) => {
                 for object in output.contents() {
                     if let Some(key) = &object.key {
-                        if key.ends_with('/') {
+                        if is_directory_marker(key, &s3_dir_str, options.trailing_slash) {
                             matches.push(key.to_string());
                         }
                     }
@@ -137,23 +173,205 @@ pub async fn create_calver_s3_directories(
     }
 
     // Create the folder object.
+    let key = if options.trailing_slash {
+        format!("{s3_dir_str}/")
+    } else {
+        s3_dir_str.clone()
+    };
     let res = client
         .put_object()
         .bucket(bucket_name)
-        .key(format!("{s3_dir_str}/",))
+        .key(key)
         .body(ByteStream::new(SdkBody::from("")))
         .send()
         .await;
     match res {
         Ok(_) => Ok(PathBuf::from(s3_dir_str)),
-        Err(e) => Err(Error::BNAAWS(super::AWSError::S3Error(e.to_string()))),
+        Err(e) => Err(Error::BNAAWS(AWSError::from_s3(e))),
+    }
+}
+
+/// Return the content type of a file, based on its extension.
+///
+/// Unknown extensions are reported as `application/octet-stream`.
+///
+/// ```
+/// use bnacore::aws::s3::content_type;
+/// use std::path::Path;
+///
+/// assert_eq!(content_type(Path::new("austin.pdf")), "application/pdf");
+/// ```
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("csv") => "text/csv",
+        Some("geojson") => "application/geo+json",
+        Some("html") => "text/html",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("txt") => "text/plain",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Upload a local file to `s3://{bucket}/{key}`.
+///
+/// The file is streamed from the disk, and its content type is deduced from
+/// its extension with [`content_type`].
+pub async fn upload_file(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    key: &str,
+    path: &Path,
+) -> Result<(), Error> {
+    let body = ByteStream::from_path(path)
+        .await
+        .map_err(|e| byte_stream_error(e, false))?;
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type(content_type(path))
+        .body(body)
+        .send()
+        .await
+        .map_err(AWSError::from_s3)?;
+    Ok(())
+}
+
+/// Convert an error occurring while streaming an object body.
+///
+/// Reading the body of a response can be retried, unlike reading a local file.
+fn byte_stream_error(err: ByteStreamError, retryable: bool) -> AWSError {
+    AWSError::S3Error {
+        code: None,
+        message: err.to_string(),
+        retryable,
+        source: Box::new(err),
+    }
+}
+
+/// Download an S3 object into memory.
+///
+/// Returns [`AWSError::S3ObjectNotFound`] if
+/// the object does not exist.
+pub async fn fetch_s3_object_as_bytes(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Vec<u8>, Error> {
+    let mut object = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| {
+            if e.as_service_error().is_some_and(|e| e.is_no_such_key()) {
+                AWSError::S3ObjectNotFound {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                }
+            } else {
+                AWSError::from_s3(e)
+            }
+        })?;
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(bytes) = object
+        .body
+        .try_next()
+        .await
+        .map_err(|e| byte_stream_error(e, true))?
+    {
+        buffer.extend_from_slice(&bytes);
+    }
+    Ok(buffer)
+}
+
+/// Download an S3 object and decode it as UTF-8 text.
+pub async fn fetch_s3_object_as_string(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    key: &str,
+) -> Result<String, Error> {
+    let buffer = fetch_s3_object_as_bytes(client, bucket, key).await?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// Return true if the key is the placeholder object of a directory starting
+/// with `prefix`.
+///
+/// Without trailing slashes, the placeholder objects are the keys without any
+/// separator after the prefix.
+fn is_directory_marker(key: &str, prefix: &str, trailing_slash: bool) -> bool {
+    if trailing_slash {
+        return key.ends_with('/');
     }
+    key.strip_prefix(prefix)
+        .is_some_and(|rest| !rest.contains('/'))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aws_sdk_s3::config::{Credentials, Region};
     use rstest::rstest;
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    /// Create an S3 client querying a mock server.
+    fn mock_s3_client(server: &MockServer) -> aws_sdk_s3::Client {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-west-2"))
+            .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+            .endpoint_url(server.uri())
+            .force_path_style(true)
+            .build();
+        aws_sdk_s3::Client::from_conf(config)
+    }
+
+    /// Mount the mocks listing the `keys` and accepting a new directory.
+    async fn mount_s3(server: &MockServer, prefix: &str, keys: &[String]) {
+        let contents = keys
+            .iter()
+            .map(|key| format!("<Contents><Key>{key}</Key></Contents>"))
+            .collect::<String>();
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Name>bucket</Name><Prefix>{prefix}</Prefix><KeyCount>{}</KeyCount><MaxKeys>1000</MaxKeys><IsTruncated>false</IsTruncated>{contents}</ListBucketResult>"#,
+            keys.len()
+        );
+        Mock::given(method("GET"))
+            .and(path("/bucket/"))
+            .and(query_param("list-type", "2"))
+            .and(query_param("prefix", prefix))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/xml"))
+            .mount(server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(server)
+            .await;
+    }
+
+    /// Return the key of the object created on the mock server.
+    async fn created_key(server: &MockServer) -> String {
+        let requests = server.received_requests().await.unwrap();
+        let put = requests
+            .iter()
+            .find(|r| r.method == wiremock::http::Method::PUT)
+            .expect("an object must have been created");
+        put.url.path().trim_start_matches("/bucket/").to_string()
+    }
 
     #[rstest]
     #[case("country", Some("region"), "city", None, None, "country/region/city")]
@@ -192,8 +410,161 @@ mod tests {
     #[case(vec![PathBuf::from("country/region/city/22.01")], 1)]
     #[case(vec![PathBuf::from("country/region/city/22.01.6")], 7)]
     #[case(vec![PathBuf::from("country/region/city/22.02"), PathBuf::from("country/region/city/22.02.1"), PathBuf::from("country/region/city/22.02.2")], 3)]
+    #[case(vec![PathBuf::from("country/region/city/some.random.5")], 1)]
+    #[case(vec![PathBuf::from("country/region/city/22.13.5")], 1)]
+    #[case(vec![PathBuf::from("country/region/city/22.02.1"), PathBuf::from("country/region/city/some.random.5")], 2)]
     fn test_calver_next(#[case] dirs: Vec<PathBuf>, #[case] expected: u32) {
         let actual = calver_next(&dirs);
         assert_eq!(actual, expected)
     }
+
+    #[tokio::test]
+    async fn test_create_calver_s3_directories_default() {
+        let server = MockServer::start().await;
+        let base = format!("testland/testregion/testville/{}", calver_utc_now());
+        mount_s3(&server, &base, &[]).await;
+
+        let dir = create_calver_s3_directories_with(
+            &mock_s3_client(&server),
+            "bucket",
+            "testland",
+            "testville",
+            Some("testregion"),
+            &S3DirectoryOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(dir, PathBuf::from(&base));
+        assert_eq!(created_key(&server).await, format!("{base}/"));
+    }
+
+    #[tokio::test]
+    async fn test_create_calver_s3_directories_prefix_without_slash() {
+        let server = MockServer::start().await;
+        let base = format!("results/testland/testregion/testville/{}", calver_utc_now());
+        mount_s3(
+            &server,
+            &base,
+            &[
+                base.clone(),
+                format!("{base}.1"),
+                format!("{base}.1/data.zip"),
+            ],
+        )
+        .await;
+
+        let options = S3DirectoryOptions {
+            key_prefix: Some("results".to_string()),
+            trailing_slash: false,
+        };
+        let dir = create_calver_s3_directories_with(
+            &mock_s3_client(&server),
+            "bucket",
+            "testland",
+            "testville",
+            Some("testregion"),
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(dir, PathBuf::from(format!("{base}.2")));
+        assert_eq!(created_key(&server).await, format!("{base}.2"));
+    }
+
+    #[rstest]
+    #[case("a/b/24.10/", "a/b/24.10", true, true)]
+    #[case("a/b/24.10/data.zip", "a/b/24.10", true, false)]
+    #[case("a/b/24.10.1", "a/b/24.10", false, true)]
+    #[case("a/b/24.10.1/data.zip", "a/b/24.10", false, false)]
+    fn test_is_directory_marker(
+        #[case] key: &str,
+        #[case] prefix: &str,
+        #[case] trailing_slash: bool,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(is_directory_marker(key, prefix, trailing_slash), expected);
+    }
+
+    #[rstest]
+    #[case("brochures/austin.pdf", "application/pdf")]
+    #[case("ratings/city_ratings.CSV", "text/csv")]
+    #[case("all.zip", "application/zip")]
+    #[case("visuals/scorecard.svg", "image/svg+xml")]
+    #[case("README", "application/octet-stream")]
+    #[case("archive.tar.gz", "application/octet-stream")]
+    fn test_content_type(#[case] path: &str, #[case] expected: &str) {
+        assert_eq!(content_type(Path::new(path)), expected);
+    }
+
+    #[tokio::test]
+    async fn test_upload_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/bucket/brochures/austin.csv"))
+            .and(header("content-type", "text/csv"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("austin.csv");
+        std::fs::write(&file, "city,score\naustin,42\n").unwrap();
+        upload_file(
+            &mock_s3_client(&server),
+            "bucket",
+            "brochures/austin.csv",
+            &file,
+        )
+        .await
+        .unwrap();
+
+        // The body may be chunk-encoded, therefore only look for the content.
+        let requests = server.received_requests().await.unwrap();
+        let content = b"city,score\naustin,42\n";
+        assert!(requests[0]
+            .body
+            .windows(content.len())
+            .any(|w| w == content));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_s3_object_as_string() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/bucket/usa/nm/santa_rosa/24.05/scores.csv"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("score_id,score\n"))
+            .mount(&server)
+            .await;
+
+        let content = fetch_s3_object_as_string(
+            &mock_s3_client(&server),
+            "bucket",
+            "usa/nm/santa_rosa/24.05/scores.csv",
+        )
+        .await
+        .unwrap();
+        assert_eq!(content, "score_id,score\n");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_s3_object_as_bytes_not_found() {
+        let server = MockServer::start().await;
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>NoSuchKey</Code><Message>The specified key does not exist.</Message><Key>missing.csv</Key></Error>"#;
+        Mock::given(method("GET"))
+            .and(path("/bucket/missing.csv"))
+            .respond_with(ResponseTemplate::new(404).set_body_raw(body, "application/xml"))
+            .mount(&server)
+            .await;
+
+        let err = fetch_s3_object_as_bytes(&mock_s3_client(&server), "bucket", "missing.csv")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BNAAWS(crate::aws::AWSError::S3ObjectNotFound { ref bucket, ref key })
+                if bucket == "bucket" && key == "missing.csv"
+        ));
+    }
 }
@@ -1,8 +1,10 @@
-use crate::Error;
+use crate::{versioning::Calver, Error};
 use libflate::gzip::{EncodeOptions, Encoder};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     ffi::OsStr,
+    fmt,
     fs::{self, File},
     io::{self, ErrorKind, Read, Write},
     path::{Path, PathBuf},
@@ -16,6 +18,8 @@ pub struct Bundle {
     pub group_by: GroupBy,
     pub strict: bool,
     pub filetype: FileType,
+    /// Add a `manifest.json` file describing the content of each zip archive.
+    pub with_manifest: bool,
 }
 
 /// Define the different ways to groups city rating brochures.
@@ -23,6 +27,12 @@ pub enum GroupBy {
     City,
     Country,
     State,
+    /// Group by the year of the calver version embedded in the file name.
+    ///
+    /// The files must follow the `<country>-<state>-<city>-<version>.<extension>`
+    /// format, where `<version>` is an Ubuntu-like calver (`YY.0M[.Micro]`),
+    /// optionally prefixed with a `v`, e.g. `united_states-tx-austin-v24.05.pdf`.
+    Year,
 }
 
 pub enum FileType {
@@ -30,6 +40,58 @@ pub enum FileType {
     Pdf,
 }
 
+/// Summarize a bundling operation.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BundleReport {
+    /// Number of groups (archives) created.
+    pub groups: usize,
+    /// Number of files added to the archives.
+    pub files_bundled: usize,
+    /// Number of files skipped because their names did not match the BNA convention.
+    pub files_skipped: usize,
+}
+
+impl BundleReport {
+    fn new(groups: &HashMap<String, Vec<PathBuf>>, files_skipped: usize) -> Self {
+        BundleReport {
+            groups: groups.len(),
+            files_bundled: groups.values().map(|files| files.len()).sum(),
+            files_skipped,
+        }
+    }
+}
+
+impl fmt::Display for BundleReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} file(s) bundled into {} group(s), {} file(s) skipped",
+            self.files_bundled, self.groups, self.files_skipped
+        )
+    }
+}
+
+/// Define the name of the manifest file added to the archives.
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Describe the content of an archive.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    /// Key of the group the archive was created for (country, state, city...).
+    pub group: String,
+    /// Members of the archive.
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Describe a member of an archive.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    /// Name of the file within the archive.
+    pub filename: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+}
+
 impl Bundle {
     /// Group file names by [`GroupBy`], usually country or state.
     ///
@@ -54,7 +116,7 @@ impl Bundle {
     /// country_groups.insert(String::from("england"), vec![PathBuf::from("england-eng-london.pdf")]);
     /// country_groups.insert(String::from("france"), vec![PathBuf::from("france-idf-paris.pdf")]);
     /// country_groups.insert(String::from("united_states"), vec![PathBuf::from("united_states-ca-arcata.pdf"), PathBuf::from("united_states-fl-altamonte_springs.pdf")]);
-    /// let bundle = Bundle {input_dir: PathBuf::from("."), group_by: GroupBy::Country, strict: true, filetype: FileType::Pdf};
+    /// let bundle = Bundle {input_dir: PathBuf::from("."), group_by: GroupBy::Country, strict: true, filetype: FileType::Pdf, with_manifest: false};
     /// let groups = bundle.group(&files).unwrap();
     /// assert_eq!(country_groups, groups);
     /// ````
@@ -89,19 +151,35 @@ impl Bundle {
     /// country_groups.insert(String::from("england"), vec![PathBuf::from("england-eng-london.pdf")]);
     /// country_groups.insert(String::from("france"), vec![PathBuf::from("france-idf-paris.pdf")]);
     /// country_groups.insert(String::from("united_states"), vec![PathBuf::from("united_states-ca-arcata.pdf"), PathBuf::from("united_states-fl-altamonte_springs.pdf")]);
-    /// let bundle = Bundle {input_dir: PathBuf::from("."), group_by: GroupBy::Country, strict: true, filetype: FileType::Pdf};
+    /// let bundle = Bundle {input_dir: PathBuf::from("."), group_by: GroupBy::Country, strict: true, filetype: FileType::Pdf, with_manifest: false};
     /// let groups = bundle.group_files(&files).unwrap();
     /// assert_eq!(country_groups, groups);
     /// ````
     pub fn group_files(&self, paths: &[PathBuf]) -> Result<HashMap<String, Vec<PathBuf>>, Error> {
+        let (groups, _skipped) = self.group_files_with_skipped(paths)?;
+        Ok(groups)
+    }
+
+    /// Group file names by [`GroupBy`] and count the files which were skipped.
+    ///
+    /// In strict mode, a file which does not match the expected format is an
+    /// error. Otherwise it is skipped.
+    fn group_files_with_skipped(
+        &self,
+        paths: &[PathBuf],
+    ) -> Result<(HashMap<String, Vec<PathBuf>>, usize), Error> {
         let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        let mut skipped: usize = 0;
         for path in paths {
-            let filename = path.file_name().map(|f| f.to_str()).unwrap().unwrap();
-            let bna_filename = BNAFilename::parse(filename)?;
-            let key = match self.group_by {
-                GroupBy::City => format!("{}-{}", bna_filename.city, bna_filename.state,),
-                GroupBy::Country => bna_filename.country,
-                GroupBy::State => bna_filename.state,
+            let key = match self.group_key(path) {
+                Ok(key) => key,
+                Err(e) => {
+                    if self.strict {
+                        return Err(e);
+                    }
+                    skipped += 1;
+                    continue;
+                }
             };
 
             groups
@@ -109,11 +187,43 @@ impl Bundle {
                 .and_modify(|g| g.push(path.to_path_buf()))
                 .or_insert_with(|| vec![path.to_path_buf()]);
         }
-        Ok(groups)
+        Ok((groups, skipped))
+    }
+
+    /// Compute the key of the group a file belongs to.
+    fn group_key(&self, path: &Path) -> Result<String, Error> {
+        let filename = match path.file_name().and_then(|f| f.to_str()) {
+            Some(filename) => filename,
+            None => {
+                return Err(Error::IOError(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("this path has no valid UTF-8 file name: {}", path.display()),
+                )))
+            }
+        };
+        let bna_filename = BNAFilename::parse(filename)?;
+        let key = match self.group_by {
+            GroupBy::City => format!("{}-{}", bna_filename.city, bna_filename.state,),
+            GroupBy::Country => bna_filename.country,
+            GroupBy::State => bna_filename.state,
+            GroupBy::Year => match bna_filename.version {
+                Some(version) => version.year().to_string(),
+                None => {
+                    return Err(Error::IOError(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("this file name does not contain a version: {filename}"),
+                    )))
+                }
+            },
+        };
+        Ok(key)
     }
 
     /// Creates a zip file for each group, as well as a zip file for all the files.
-    pub fn zip(&self, bundle_all: bool) -> Result<(), Error> {
+    ///
+    /// If `with_manifest` is set, a [`MANIFEST_FILENAME`] file listing the
+    /// members of each group archive is added to it.
+    pub fn zip(&self, bundle_all: bool) -> Result<BundleReport, Error> {
         // Collect the files.
         let collected_files = match self.filetype {
             FileType::All => self.gather_all_files(),
@@ -121,7 +231,7 @@ impl Bundle {
         };
 
         // Group the files.
-        let groups = self.group_files(&collected_files)?;
+        let (groups, skipped) = self.group_files_with_skipped(&collected_files)?;
 
         // Create a "bundles" directory to store the bundles.
         let bundle_dir = self.input_dir.join("bundles");
@@ -140,10 +250,14 @@ impl Bundle {
         // Zip each group.
         for (group_name, files) in groups.iter() {
             // Zip the group.
-            let group_name = format!("{group_name}.zip");
-            let group_path = bundle_dir.join(group_name);
+            let archive_name = format!("{group_name}.zip");
+            let group_path = bundle_dir.join(archive_name);
             let group_file = std::fs::File::create(group_path).unwrap();
             let mut group_zip = zip::ZipWriter::new(group_file);
+            let mut manifest = Manifest {
+                group: group_name.clone(),
+                entries: Vec::new(),
+            };
 
             // Add each file from the group.
             for file in files {
@@ -156,6 +270,10 @@ impl Bundle {
                 let file_name = file.file_name().map(|f| f.to_str()).unwrap().unwrap();
                 group_zip.start_file(file_name, options)?;
                 group_zip.write_all(&buffer)?;
+                manifest.entries.push(ManifestEntry {
+                    filename: file_name.to_string(),
+                    size: buffer.len() as u64,
+                });
 
                 // Add the file to the "all" archive.
                 if bundle_all {
@@ -164,13 +282,20 @@ impl Bundle {
                     all_zip.write_all(&buffer)?;
                 }
             }
+
+            // Add the manifest.
+            if self.with_manifest {
+                group_zip.start_file(MANIFEST_FILENAME, options)?;
+                serde_json::to_writer_pretty(&mut group_zip, &manifest)?;
+            }
+            group_zip.finish()?;
         }
 
-        Ok(())
+        Ok(BundleReport::new(&groups, skipped))
     }
 
     /// Creates a gzip file for each group.
-    pub fn gzip(&self) -> Result<(), Error> {
+    pub fn gzip(&self) -> Result<BundleReport, Error> {
         // Collect the files.
         let collected_files = match self.filetype {
             FileType::All => self.gather_all_files(),
@@ -178,7 +303,7 @@ impl Bundle {
         };
 
         // Group the files.
-        let groups = self.group_files(&collected_files)?;
+        let (groups, skipped) = self.group_files_with_skipped(&collected_files)?;
 
         // Create a "bundles" directory to store the bundles.
         let bundle_dir = self.input_dir.join("bundles");
@@ -206,7 +331,7 @@ impl Bundle {
             archive.finish().into_result()?;
         }
 
-        Ok(())
+        Ok(BundleReport::new(&groups, skipped))
     }
 
     /// Gather the files satisfying a filter predicate.
@@ -249,15 +374,35 @@ pub fn filter_pdf_files(entry: &DirEntry) -> bool {
     entry.metadata().unwrap().is_file() && entry.path().extension() == Some(OsStr::new("pdf"))
 }
 
+/// Represent the parts of a BNA file name.
+///
+/// The file names are expected to be in the following format:
+/// `<country>-<state>-<city>[-<version>][-<description>].<extension>`.
+///
+/// The version is optional and must be an Ubuntu-like calver (`YY.0M[.Micro]`),
+/// optionally prefixed with a `v`.
 pub struct BNAFilename {
     pub country: String,
     pub state: String,
     pub city: String,
+    pub version: Option<Calver>,
     pub description: Option<String>,
     pub extension: String,
 }
 
 impl BNAFilename {
+    /// Parse a file name into its BNA parts.
+    ///
+    /// ```rust
+    /// use bnacore::bundle::BNAFilename;
+    ///
+    /// let bna_filename = BNAFilename::parse("united_states-tx-austin-v24.05.pdf").unwrap();
+    /// assert_eq!(bna_filename.city, "austin");
+    /// assert_eq!(bna_filename.version.unwrap().to_ubuntu(), "24.05");
+    ///
+    /// let bna_filename = BNAFilename::parse("united_states-tx-austin.pdf").unwrap();
+    /// assert!(bna_filename.version.is_none());
+    /// ```
     pub fn parse(i: &str) -> Result<Self, Error> {
         let file = Path::new(i);
 
@@ -332,14 +477,122 @@ impl BNAFilename {
                 )))
             }
         };
+        let version = split_stem
+            .iter()
+            .skip(3)
+            .find_map(|part| Calver::try_from_ubuntu(part.trim_start_matches('v')).ok());
         let description = split_stem.get(4).map(|&part| String::from(part));
 
         Ok(BNAFilename {
             country,
             state,
             city,
+            version,
             description,
             extension,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by_year() {
+        let files = vec![
+            PathBuf::from("australia-nt-alice_springs-v23.1.pdf"),
+            PathBuf::from("france-idf-paris-24.05.pdf"),
+            PathBuf::from("united_states-ca-arcata-v24.01.2.pdf"),
+        ];
+        let bundle = Bundle {
+            input_dir: PathBuf::from("."),
+            group_by: GroupBy::Year,
+            strict: true,
+            filetype: FileType::Pdf,
+            with_manifest: false,
+        };
+        let groups = bundle.group_files(&files).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups["2023"],
+            vec![PathBuf::from("australia-nt-alice_springs-v23.1.pdf")]
+        );
+        assert_eq!(
+            groups["2024"],
+            vec![
+                PathBuf::from("france-idf-paris-24.05.pdf"),
+                PathBuf::from("united_states-ca-arcata-v24.01.2.pdf")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_by_year_without_version() {
+        let files = vec![PathBuf::from("france-idf-paris.pdf")];
+        let bundle = Bundle {
+            input_dir: PathBuf::from("."),
+            group_by: GroupBy::Year,
+            strict: true,
+            filetype: FileType::Pdf,
+            with_manifest: false,
+        };
+        assert!(bundle.group_files(&files).is_err());
+    }
+
+    #[test]
+    fn test_zip_with_manifest() {
+        let input_dir = tempfile::tempdir().unwrap();
+        fs::write(input_dir.path().join("france-idf-paris.pdf"), b"paris").unwrap();
+        fs::write(input_dir.path().join("france-ara-lyon.pdf"), b"lyon!").unwrap();
+        fs::write(input_dir.path().join("england-eng-london.pdf"), b"london").unwrap();
+        let bundle = Bundle {
+            input_dir: input_dir.path().to_path_buf(),
+            group_by: GroupBy::Country,
+            strict: true,
+            filetype: FileType::Pdf,
+            with_manifest: true,
+        };
+        bundle.zip(false).unwrap();
+
+        let archive_file = File::open(input_dir.path().join("bundles/france.zip")).unwrap();
+        let mut archive = zip::ZipArchive::new(archive_file).unwrap();
+        let manifest: Manifest =
+            serde_json::from_reader(archive.by_name(MANIFEST_FILENAME).unwrap()).unwrap();
+        assert_eq!(manifest.group, "france");
+        assert_eq!(manifest.entries.len(), 2);
+        assert_eq!(manifest.entries.len(), archive.len() - 1);
+        for entry in manifest.entries {
+            let member = archive.by_name(&entry.filename).unwrap();
+            assert_eq!(member.size(), entry.size);
+        }
+    }
+
+    #[test]
+    fn test_zip_report() {
+        let input_dir = tempfile::tempdir().unwrap();
+        fs::write(input_dir.path().join("france-idf-paris.pdf"), b"paris").unwrap();
+        fs::write(input_dir.path().join("france-ara-lyon.pdf"), b"lyon").unwrap();
+        fs::write(input_dir.path().join("england-eng-london.pdf"), b"london").unwrap();
+        fs::write(input_dir.path().join("README.pdf"), b"readme").unwrap();
+        let mut bundle = Bundle {
+            input_dir: input_dir.path().to_path_buf(),
+            group_by: GroupBy::Country,
+            strict: false,
+            filetype: FileType::Pdf,
+            with_manifest: false,
+        };
+        let report = bundle.zip(false).unwrap();
+        assert_eq!(
+            report,
+            BundleReport {
+                groups: 2,
+                files_bundled: 3,
+                files_skipped: 1
+            }
+        );
+
+        bundle.strict = true;
+        assert!(bundle.zip(false).is_err());
+    }
+}
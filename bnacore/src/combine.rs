@@ -1,5 +1,6 @@
+use crate::Error;
 use lopdf::{Bookmark, Document, Object, ObjectId};
-use std::{collections::BTreeMap, io::Result, path::Path};
+use std::{collections::BTreeMap, io::Result, ops::Range, path::Path};
 
 /// Merge PDF files together, in the order they are being provided.
 ///
@@ -7,16 +8,27 @@ use std::{collections::BTreeMap, io::Result, path::Path};
 /// from the `lopdf` README.md:
 /// https://github.com/J-F-Liu/lopdf/blob/850b150461245cbf7c8dd780b31c76837769a0f5/README.md
 pub fn combine_documents(documents: Vec<Document>) -> Result<Document> {
+    combine_labeled_documents(
+        documents
+            .into_iter()
+            .enumerate()
+            .map(|(i, doc)| (format!("Page_{}", i + 1), doc))
+            .collect(),
+    )
+}
+
+/// Merge PDF documents together, adding a top-level bookmark with the provided
+/// label on the first page of each document.
+fn combine_labeled_documents(documents: Vec<(String, Document)>) -> Result<Document> {
     // Define a starting max_id (will be used as start index for object_ids)
     let mut max_id = 1;
-    let mut pagenum = 1;
 
     // Collect all Documents Objects grouped by a map
     let mut documents_pages = BTreeMap::new();
     let mut documents_objects = BTreeMap::new();
     let mut document = Document::with_version("1.5");
 
-    for mut doc in documents {
+    for (label, mut doc) in documents {
         let mut first = false;
         doc.renumber_objects_with(max_id);
 
@@ -27,15 +39,9 @@ pub fn combine_documents(documents: Vec<Document>) -> Result<Document> {
                 .into_values()
                 .map(|object_id| {
                     if !first {
-                        let bookmark = Bookmark::new(
-                            format!("Page_{}", pagenum),
-                            [0.0, 0.0, 1.0],
-                            0,
-                            object_id,
-                        );
+                        let bookmark = Bookmark::new(label.clone(), [0.0, 0.0, 1.0], 0, object_id);
                         document.add_bookmark(bookmark, None);
                         first = true;
-                        pagenum += 1;
                     }
 
                     (object_id, doc.get_object(object_id).unwrap().to_owned())
@@ -188,29 +194,333 @@ pub fn combine_mem(documents: &[&[u8]]) -> Result<Document> {
     combine_documents(docs)
 }
 
-pub fn combine_pdf<P>(documents: &[&Path], output: P) -> Result<()>
+/// Append the `extra` in-memory PDF to the `base` in-memory PDF.
+///
+/// The merged document is returned as bytes, without touching the file system.
+pub fn append_mem(base: &[u8], extra: &[u8]) -> std::result::Result<Vec<u8>, Error> {
+    let docs = vec![Document::load_mem(base)?, Document::load_mem(extra)?];
+    let mut combined = combine_documents(docs)?;
+    let mut buffer: Vec<u8> = Vec::new();
+    combined.save_to(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Return the number of pages of a PDF file.
+pub fn page_count(path: &Path) -> std::result::Result<usize, Error> {
+    let doc = Document::load(path)?;
+    Ok(doc.get_pages().len())
+}
+
+/// Extract page ranges of a PDF file into separate in-memory PDFs.
+///
+/// The ranges are 0-based and exclusive, meaning that `0..1` extracts the first
+/// page only. A document is returned for each range, in the same order.
+///
+/// An empty range, or a range going beyond the last page of the document, is
+/// rejected with an [`Error::InvalidArgument`].
+///
+/// ```no_run
+/// use bnacore::combine::split;
+/// use std::path::Path;
+///
+/// // Extract the first page of a brochure.
+/// let pages = split(Path::new("brochure.pdf"), &[0..1]).unwrap();
+/// ```
+pub fn split(input: &Path, ranges: &[Range<usize>]) -> std::result::Result<Vec<Vec<u8>>, Error> {
+    let doc = Document::load(input)?;
+    let page_count = doc.get_pages().len();
+
+    // Validate all the ranges before extracting anything.
+    if let Some(range) = ranges
+        .iter()
+        .find(|range| range.is_empty() || range.end > page_count)
+    {
+        return Err(Error::InvalidArgument(format!(
+            "invalid page range {range:?} for a document with {page_count} page(s)"
+        )));
+    }
+
+    ranges
+        .iter()
+        .map(|range| {
+            // Page numbers are 1-based in lopdf.
+            let pages_to_delete = (1..=page_count)
+                .filter(|page| !range.contains(&(page - 1)))
+                .map(|page| page as u32)
+                .collect::<Vec<u32>>();
+            let mut extract = doc.clone();
+            extract.delete_pages(&pages_to_delete);
+            extract.prune_objects();
+            extract.compress();
+
+            let mut buffer: Vec<u8> = Vec::new();
+            extract.save_to(&mut buffer)?;
+            Ok(buffer)
+        })
+        .collect()
+}
+
+/// Check whether a buffer contains a PDF document which can be loaded and
+/// which contains at least one page.
+pub fn is_valid_pdf(bytes: &[u8]) -> bool {
+    Document::load_mem(bytes).is_ok_and(|doc| !doc.get_pages().is_empty())
+}
+
+/// Merge the PDF files `documents` into the `output` file, see [`combine`].
+pub fn combine_pdf<P>(documents: &[&Path], output: P) -> std::result::Result<(), Error>
 where
     P: AsRef<Path>,
 {
-    // Load the files.
-    let docs = documents
-        .iter()
-        .map(|d| Document::load(d).unwrap())
-        .collect::<Vec<Document>>();
+    combine(documents, output.as_ref())
+}
 
-    // Merge the files.
+/// Merge the PDF files `inputs` into the `output` file, in the order they are
+/// being provided.
+///
+/// An input which cannot be loaded is reported as an error, and the output
+/// file is not written.
+///
+/// ```no_run
+/// use bnacore::combine::combine;
+/// use std::path::Path;
+///
+/// combine(
+///     &[Path::new("page-1.pdf"), Path::new("page-2.pdf")],
+///     Path::new("brochure.pdf"),
+/// )
+/// .unwrap();
+/// ```
+pub fn combine(inputs: &[&Path], output: &Path) -> std::result::Result<(), Error> {
+    let docs = inputs
+        .iter()
+        .map(Document::load)
+        .collect::<std::result::Result<Vec<Document>, lopdf::Error>>()?;
     let mut combined = combine_documents(docs)?;
+    combined.save(output)?;
+    Ok(())
+}
 
-    // Save the merged PDF.
+/// Merge the labeled PDF files `inputs` into the `output` file, in the order
+/// they are being provided.
+///
+/// A top-level outline entry is added for each input, pointing at its first
+/// page and using its label as title, which makes it easy to navigate a bundle
+/// of brochures.
+///
+/// ```no_run
+/// use bnacore::combine::combine_with_bookmarks;
+/// use std::path::Path;
+///
+/// combine_with_bookmarks(
+///     &[
+///         ("Christchurch".to_string(), Path::new("christchurch.pdf")),
+///         ("Wellington".to_string(), Path::new("wellington.pdf")),
+///     ],
+///     Path::new("new_zealand.pdf"),
+/// )
+/// .unwrap();
+/// ```
+pub fn combine_with_bookmarks(
+    inputs: &[(String, &Path)],
+    output: &Path,
+) -> std::result::Result<(), Error> {
+    let docs = inputs
+        .iter()
+        .map(|(label, path)| Ok((label.clone(), Document::load(path)?)))
+        .collect::<std::result::Result<Vec<(String, Document)>, lopdf::Error>>()?;
+    let mut combined = combine_labeled_documents(docs)?;
     combined.save(output)?;
     Ok(())
 }
 
 /// Append the `extra` document to all the `documents`.
-pub fn batch_append(documents: &[&Path], extra: &Path) -> Result<()> {
+///
+/// The operation stops at the first document which cannot be loaded, leaving
+/// it and the next ones untouched.
+pub fn batch_append(documents: &[&Path], extra: &Path) -> std::result::Result<(), Error> {
     for &document in documents {
-        combine_pdf(&[document, extra], document)?;
+        combine(&[document, extra], document)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{
+        content::{Content, Operation},
+        dictionary, Stream,
+    };
+    use rstest::rstest;
+
+    /// Generate a PDF document with `page_count` empty pages.
+    fn generate_pdf(page_count: u32) -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids = (0..page_count)
+            .map(|_| {
+                let content = Content {
+                    operations: vec![Operation::new("BT", vec![]), Operation::new("ET", vec![])],
+                };
+                let content_id =
+                    doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+                let page_id = doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                });
+                page_id.into()
+            })
+            .collect::<Vec<Object>>();
+        let pages = dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => page_count,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        };
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        let mut buffer: Vec<u8> = Vec::new();
+        doc.save_to(&mut buffer).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_append_mem() {
+        let base = generate_pdf(2);
+        let extra = generate_pdf(1);
+        let merged = append_mem(&base, &extra).unwrap();
+        let doc = Document::load_mem(&merged).unwrap();
+        assert_eq!(doc.get_pages().len(), 3);
+    }
+
+    #[test]
+    fn test_combine() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("two-pages.pdf");
+        let second = dir.path().join("three-pages.pdf");
+        let output = dir.path().join("combined.pdf");
+        std::fs::write(&first, generate_pdf(2)).unwrap();
+        std::fs::write(&second, generate_pdf(3)).unwrap();
+
+        combine(&[&first, &second], &output).unwrap();
+        assert_eq!(page_count(&output).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_combine_invalid_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid = dir.path().join("two-pages.pdf");
+        let invalid = dir.path().join("invalid.pdf");
+        let output = dir.path().join("combined.pdf");
+        std::fs::write(&valid, generate_pdf(2)).unwrap();
+        std::fs::write(&invalid, b"this is definitely not a PDF document").unwrap();
+
+        assert!(combine(&[&valid, &invalid], &output).is_err());
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_batch_append_invalid_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid = dir.path().join("two-pages.pdf");
+        let invalid = dir.path().join("invalid.pdf");
+        let extra = dir.path().join("extra.pdf");
+        std::fs::write(&valid, generate_pdf(2)).unwrap();
+        std::fs::write(&invalid, b"this is definitely not a PDF document").unwrap();
+        std::fs::write(&extra, generate_pdf(1)).unwrap();
+
+        let err = batch_append(&[&valid, &invalid], &extra).unwrap_err();
+        assert!(matches!(err, Error::Pdf(_)));
+        assert_eq!(page_count(&valid).unwrap(), 3);
+    }
+
+    /// Collect the titles of the top-level outline items of a PDF document.
+    fn outline_titles(doc: &Document) -> Vec<String> {
+        let outlines = doc
+            .catalog()
+            .unwrap()
+            .get(b"Outlines")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .unwrap();
+        let mut titles = Vec::new();
+        let mut item = outlines.get(b"First").and_then(Object::as_reference).ok();
+        while let Some(id) = item {
+            let dict = doc.get_dictionary(id).unwrap();
+            let title = dict.get(b"Title").and_then(Object::as_str).unwrap();
+            titles.push(String::from_utf8_lossy(title).to_string());
+            item = dict.get(b"Next").and_then(Object::as_reference).ok();
+        }
+        titles
+    }
+
+    #[test]
+    fn test_combine_with_bookmarks() {
+        let dir = tempfile::tempdir().unwrap();
+        let christchurch = dir.path().join("christchurch.pdf");
+        let wellington = dir.path().join("wellington.pdf");
+        let output = dir.path().join("new_zealand.pdf");
+        std::fs::write(&christchurch, generate_pdf(2)).unwrap();
+        std::fs::write(&wellington, generate_pdf(2)).unwrap();
+
+        combine_with_bookmarks(
+            &[
+                ("Christchurch".to_string(), christchurch.as_path()),
+                ("Wellington".to_string(), wellington.as_path()),
+            ],
+            &output,
+        )
+        .unwrap();
+        let doc = Document::load(&output).unwrap();
+        assert_eq!(doc.get_pages().len(), 4);
+        assert_eq!(outline_titles(&doc), vec!["Christchurch", "Wellington"]);
+    }
+
+    #[test]
+    fn test_page_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("two-pages.pdf");
+        std::fs::write(&path, generate_pdf(2)).unwrap();
+        assert_eq!(page_count(&path).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("five-pages.pdf");
+        std::fs::write(&path, generate_pdf(5)).unwrap();
+
+        let extracts = split(&path, &[0..1, 1..4, 4..5]).unwrap();
+        let page_counts = extracts
+            .iter()
+            .map(|extract| Document::load_mem(extract).unwrap().get_pages().len())
+            .collect::<Vec<usize>>();
+        assert_eq!(page_counts, vec![1, 3, 1]);
+    }
+
+    #[rstest]
+    #[case::beyond_last_page(4..6)]
+    #[case::after_last_page(5..6)]
+    #[case::empty(2..2)]
+    fn test_split_invalid_range(#[case] range: Range<usize>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("five-pages.pdf");
+        std::fs::write(&path, generate_pdf(5)).unwrap();
+
+        let err = split(&path, &[0..1, range]).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn test_is_valid_pdf() {
+        assert!(is_valid_pdf(&generate_pdf(2)));
+        assert!(!is_valid_pdf(b"this is definitely not a PDF document"));
+    }
+}
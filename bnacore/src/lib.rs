@@ -8,7 +8,7 @@ pub mod scorecard;
 pub mod template;
 pub mod versioning;
 
-use std::{fmt, io};
+use std::{fmt, io, time::Duration};
 use thiserror::Error;
 
 /// Represent the PFB S3 storage base URL.
@@ -26,6 +26,37 @@ pub const MAX_PROMPT_LENGTH: usize = 8191;
 /// There is no length limit for the command prompt on Unix platforms.
 pub const MAX_PROMPT_LENGTH: usize = usize::MAX;
 
+/// Define the timeouts of the HTTP clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpTimeouts {
+    /// Timeout for establishing a connection.
+    pub connect: Duration,
+    /// Timeout for a whole request, from connecting to reading the response.
+    pub total: Duration,
+}
+
+impl Default for HttpTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(10),
+            total: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Maximum delay between two retries of a request.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Return the delay before retrying a request for the `attempt`-th time.
+///
+/// The delay starts at `base_delay`, doubles after each attempt, and is capped
+/// at [`MAX_BACKOFF`].
+pub fn backoff_delay(base_delay: Duration, attempt: u32) -> Duration {
+    2u32.checked_pow(attempt)
+        .and_then(|factor| base_delay.checked_mul(factor))
+        .map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+}
+
 /// Errors that can happen when using bnacore.
 #[derive(Error, Debug)]
 pub enum Error {
@@ -76,6 +107,26 @@ pub enum Error {
     /// Environment variable error.
     #[error("Environment variable error")]
     VarError(#[from] std::env::VarError),
+
+    /// Error from the lopdf crate.
+    #[error("PDF error")]
+    Pdf(#[from] lopdf::Error),
+
+    /// Error from the Rayon crate.
+    #[error("Thread pool error")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+
+    /// Error from the svg2pdf crate.
+    #[error("SVG to PDF conversion error: {0}")]
+    Svg2Pdf(String),
+
+    /// Error while decoding UTF-8 bytes.
+    #[error("UTF-8 error")]
+    Utf8(#[from] std::string::FromUtf8Error),
+
+    /// Error while decoding base64 data.
+    #[error("Base64 decoding error: {0}")]
+    Base64(#[from] base64::DecodeError),
 }
 
 /// Describe all the available city datasets.
@@ -151,6 +202,11 @@ impl Dataset {
 
 /// Build commands.
 ///
+/// Split the `positionals` into groups, so that each command made of the
+/// `program`, the `flags` and a group of positional arguments fits within
+/// `limit`. Returns [`Error::PromptTooLong`] if the program and the flags alone
+/// do not fit.
+///
 /// ```
 /// use bnacore::build_cmd_args;
 /// let cmds = build_cmd_args(
@@ -166,7 +222,7 @@ impl Dataset {
 ///       "united_states-tx-austin.svg".to_string(),
 ///       "united_states-tx-houston.svg".to_string(),
 ///   ],
-///   112,
+///   130,
 /// ).unwrap();
 /// assert_eq!(
 ///   cmds,
@@ -176,18 +232,13 @@ impl Dataset {
 ///             "--batch-process".to_string(),
 ///             "--export-type=pdf".to_string(),
 ///             "canada-on-toronto.svg".to_string(),
-///         ],
-///         vec![
-///             "--export-area-drawing".to_string(),
-///             "--batch-process".to_string(),
-///             "--export-type=pdf".to_string(),
 ///             "united_states-co-boulder.svg".to_string(),
-///             "united_states-tx-austin.svg".to_string(),
 ///         ],
 ///         vec![
 ///             "--export-area-drawing".to_string(),
 ///             "--batch-process".to_string(),
 ///             "--export-type=pdf".to_string(),
+///             "united_states-tx-austin.svg".to_string(),
 ///             "united_states-tx-houston.svg".to_string(),
 ///         ],
 ///     ]
@@ -199,10 +250,14 @@ pub fn build_cmd_args(
     positionals: &[String],
     limit: usize,
 ) -> Result<Vec<Vec<String>>, Error> {
-    let program_len = program.len();
-    let flags_len: usize = flags.iter().map(|f| f.len()).sum();
+    let program_len = quoted_len(program);
+    let flags_len: usize = flags.iter().map(|f| quoted_len(f)).sum();
     let base_len = program_len + flags_len + flags.len();
 
+    // Ensure there is room left for the positional arguments.
+    if base_len >= limit {
+        return Err(Error::PromptTooLong);
+    }
     let cmd_limit = limit - base_len;
     let positional_groups = word_chunks(positionals, cmd_limit)?;
 
@@ -220,7 +275,8 @@ pub fn build_cmd_args(
 
 /// Group words into chunks of a certain size.
 ///
-/// The size of the chunks account for the space between the words.
+/// The size of the chunks account for the space between the words, and for the
+/// quotes the platform shell requires around the words (see [`quoted_len`]).
 ///
 /// ```
 /// use bnacore::word_chunks;
@@ -239,12 +295,22 @@ pub fn build_cmd_args(
 ///   chunks,
 ///   vec![
 ///     vec!["gastropub".to_string(), "shaman".to_string()],
-///     vec!["skateboard".to_string(),"succulents".to_string(),"meditation".to_string()],
-///     vec!["street".to_string()]
+///     vec!["skateboard".to_string(), "succulents".to_string()],
+///     vec!["meditation".to_string(), "street".to_string()]
 ///   ]
 /// );
 /// ```
 pub fn word_chunks(words: &[String], limit: usize) -> Result<Vec<Vec<String>>, Error> {
+    word_chunks_with(words, limit, quoted_len)
+}
+
+/// Group words into chunks of a certain size, measuring the words with
+/// `word_len_fn`.
+fn word_chunks_with(
+    words: &[String],
+    limit: usize,
+    word_len_fn: fn(&str) -> usize,
+) -> Result<Vec<Vec<String>>, Error> {
     let mut chunks: Vec<Vec<String>> = Vec::new();
     let mut chunk: Vec<String> = Vec::new();
     let mut chunk_len: usize = 0;
@@ -252,19 +318,21 @@ pub fn word_chunks(words: &[String], limit: usize) -> Result<Vec<Vec<String>>, E
     // Process the words.
     for word in words {
         // Validate the word size.
-        let word_len = word.len();
-        if word.len() > limit {
+        let word_len = word_len_fn(word);
+        if word_len > limit {
             return Err(Error::Internal(format!(
                 "The length of the word (\"{word}\" ({word_len})) exceeds the limit defined({limit})."
             )));
         }
-        if (chunk_len + word.len() + 1) < limit {
+        if (chunk_len + word_len + 1) < limit {
             chunk.push(word.clone());
-            chunk_len += word.len() + 1;
+            chunk_len += word_len + 1;
         } else {
-            chunks.push(chunk);
+            if !chunk.is_empty() {
+                chunks.push(chunk);
+            }
             chunk = vec![word.clone()];
-            chunk_len = 0;
+            chunk_len = word_len + 1;
         }
     }
     chunks.push(chunk);
@@ -272,9 +340,89 @@ pub fn word_chunks(words: &[String], limit: usize) -> Result<Vec<Vec<String>>, E
     Ok(chunks)
 }
 
+/// Compute the length of an argument once quoted for the command line.
+///
+/// Uses the cmd.exe quoting rules on Windows (see [`windows_quoted_len`]) and
+/// the POSIX shell ones on Unix (see [`posix_quoted_len`]).
+pub fn quoted_len(arg: &str) -> usize {
+    #[cfg(windows)]
+    return windows_quoted_len(arg);
+    #[cfg(not(windows))]
+    return posix_quoted_len(arg);
+}
+
+/// Compute the length of an argument once quoted for the Windows command line.
+///
+/// Arguments containing whitespace are surrounded by double quotes, the double
+/// quotes they contain are escaped with a backslash, and the backslashes
+/// preceding a double quote are doubled. The length is counted in UTF-16 code
+/// units, like the Windows limit.
+///
+/// ```
+/// use bnacore::windows_quoted_len;
+/// assert_eq!(windows_quoted_len("austin.svg"), 10);
+/// assert_eq!(windows_quoted_len("santa rosa.svg"), 16);
+/// assert_eq!(windows_quoted_len(r#"say "hi""#), 12);
+/// ```
+pub fn windows_quoted_len(arg: &str) -> usize {
+    let needs_quotes = arg.is_empty() || arg.contains([' ', '\t']);
+    let mut len = 0;
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+        } else {
+            if c == '"' {
+                len += backslashes + 1;
+            }
+            backslashes = 0;
+        }
+        len += c.len_utf16();
+    }
+    if needs_quotes {
+        // The trailing backslashes must be doubled before the closing quote.
+        len += backslashes + 2;
+    }
+    len
+}
+
+/// Compute the length of an argument once quoted for a POSIX shell.
+///
+/// Arguments containing characters other than the shell-safe ones are
+/// surrounded by single quotes, and the single quotes they contain are written
+/// as `'\''`.
+///
+/// ```
+/// use bnacore::posix_quoted_len;
+/// assert_eq!(posix_quoted_len("austin.svg"), 10);
+/// assert_eq!(posix_quoted_len("santa rosa.svg"), 16);
+/// assert_eq!(posix_quoted_len("coeur d'alene.svg"), 22);
+/// ```
+pub fn posix_quoted_len(arg: &str) -> usize {
+    let is_safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if is_safe {
+        return arg.len();
+    }
+    arg.len() + 2 + 3 * arg.matches('\'').count()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, Duration::from_millis(200))]
+    #[case(3, Duration::from_millis(1600))]
+    #[case(10, MAX_BACKOFF)]
+    #[case(32, MAX_BACKOFF)]
+    #[case(u32::MAX, MAX_BACKOFF)]
+    fn test_backoff_delay(#[case] attempt: u32, #[case] expected: Duration) {
+        assert_eq!(backoff_delay(Duration::from_millis(200), attempt), expected);
+    }
 
     #[test]
     #[should_panic(
@@ -283,4 +431,76 @@ mod tests {
     fn test_word_chunks_too_long() {
         let _chunks = word_chunks(&["gastropub".to_string()], 5).unwrap();
     }
+
+    #[test]
+    fn test_build_cmd_args_flags_too_long() {
+        let result = build_cmd_args(
+            "inkscape",
+            &[
+                "--export-area-drawing".to_string(),
+                "--batch-process".to_string(),
+            ],
+            &["united_states-tx-austin.svg".to_string()],
+            32,
+        );
+        assert!(matches!(result, Err(Error::PromptTooLong)));
+    }
+
+    #[test]
+    fn test_word_chunks_windows_quoted_len() {
+        // Windows limit for the command prompt.
+        let limit = 8191;
+        let words = (0..1000)
+            .map(|i| format!("united_states-ca-santa rosa-{i}.svg"))
+            .collect::<Vec<String>>();
+
+        let quoted_line_len = |chunk: &Vec<String>| {
+            chunk
+                .iter()
+                .map(|w| format!("\"{w}\""))
+                .collect::<Vec<String>>()
+                .join(" ")
+                .len()
+        };
+
+        let chunks = word_chunks_with(&words, limit, windows_quoted_len).unwrap();
+        assert!(chunks.iter().all(|c| quoted_line_len(c) <= limit));
+        assert_eq!(chunks.concat(), words);
+
+        // Measuring the raw length would produce oversized command lines.
+        let raw_chunks = word_chunks_with(&words, limit, str::len).unwrap();
+        assert!(raw_chunks.iter().any(|c| quoted_line_len(c) > limit));
+    }
+
+    #[rstest]
+    #[case("austin.svg", 10)]
+    #[case("santa rosa.svg", 16)]
+    #[case("", 2)]
+    #[case(r#"C:\brochures\santa rosa\"#, 27)]
+    #[case(r#"a\"b"#, 6)]
+    fn test_windows_quoted_len(#[case] arg: &str, #[case] expected: usize) {
+        assert_eq!(windows_quoted_len(arg), expected);
+    }
+
+    #[test]
+    fn test_word_chunks_within_limit() {
+        // Generate words of various lengths with a simple linear congruential
+        // generator, to keep the test deterministic.
+        let mut seed: usize = 42;
+        let words = (0..200)
+            .map(|_| {
+                seed = (seed * 1_103_515_245 + 12_345) % (1 << 31);
+                "w".repeat(1 + seed % 12)
+            })
+            .collect::<Vec<String>>();
+
+        for limit in 14..64 {
+            let chunks = word_chunks(&words, limit).unwrap();
+            for chunk in &chunks {
+                assert!(!chunk.is_empty());
+                assert!(chunk.join(" ").len() <= limit, "limit: {limit}");
+            }
+            assert_eq!(chunks.concat(), words);
+        }
+    }
 }
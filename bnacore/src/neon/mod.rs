@@ -1,18 +1,48 @@
 use reqwest::{
     self,
     header::{self, HeaderValue},
+    Response, StatusCode,
 };
+use serde::de::DeserializeOwned;
 
 use self::model::{
-    Branch, CreateBranchRequest, CreateBranchResponse, DeleteBranchResponse, Endpoint,
-    EndpointType, ListBranchResponses,
+    Branch, CreateBranchRequest, CreateBranchResponse, Database, DeleteBranchResponse, Endpoint,
+    EndpointType, GetBranchResponse, GetOperationResponse, ListBranchResponse, ListBranchResponses,
+    ListDatabasesResponse, ListEndpointsResponse, ListRolesResponse, Operation,
+    RestoreBranchRequest, RestoreBranchResponse, Role,
 };
+use crate::{
+    aws::{get_aws_parameter_value, get_aws_secrets_value, AWSError},
+    HttpTimeouts,
+};
+use std::{env, time::Duration};
 use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::time::Instant;
 
 pub mod model;
 
 pub const NEON_PROJECTS_URL: &str = "https://console.neon.tech/api/v2/projects";
 
+/// Environment variable containing the Neon API key.
+pub const NEON_API_KEY_VAR: &str = "NEON_API_KEY";
+
+/// Environment variable containing the ID of the Neon project.
+pub const NEON_PROJECT_ID_VAR: &str = "NEON_PROJECT_ID";
+
+/// AWS secret containing the Neon API key, under the key of the same name.
+pub const NEON_API_KEY_SECRET: &str = "NEON_API_KEY";
+
+/// AWS parameter containing the ID of the Neon project of the analyzer.
+pub const NEON_PROJECT_ID_PARAMETER: &str = "NEON_BROKENSPOKE_ANALYZER_PROJECT";
+
+/// Delay between two polls of an operation, unless the API suggests otherwise.
+const OPERATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Minimum delay between two polls of an operation, even when the API suggests
+/// retrying sooner.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 // Neon.tech module errors
 #[derive(Error, Debug)]
 pub enum NeonError {
@@ -23,16 +53,176 @@ pub enum NeonError {
     /// The API Key contains invalid characters.
     #[error("invalid API Key")]
     InvalidAPIKey,
+
+    /// An operation did not finish in time.
+    #[error("operation `{0}` did not finish in time")]
+    Timeout(String),
+
+    /// An operation failed.
+    #[error("operation `{id}` ended with status `{status}`")]
+    OperationFailed { id: String, status: String },
+
+    /// A configuration value required to create the client is missing.
+    #[error("missing Neon configuration `{name}`")]
+    MissingConfiguration {
+        name: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// The configuration could not be retrieved from AWS.
+    #[error("AWS error")]
+    Aws(#[from] AWSError),
+
+    /// The branch does not have a parent branch.
+    #[error("branch `{0}` does not have a parent branch")]
+    NoParentBranch(String),
+
+    /// Error returned by the Neon API.
+    ///
+    /// The `code` is only available when the error body is a Neon error.
+    /// Otherwise the `message` contains the raw body of the response.
+    #[error(
+        "Neon API error ({status}): {}{message}",
+        code.as_ref().map(|code| format!("{code}: ")).unwrap_or_default()
+    )]
+    Api {
+        code: Option<String>,
+        message: String,
+        status: StatusCode,
+    },
+}
+
+/// Parse a response from the Neon API.
+///
+/// On success, the body is deserialized into `T`. Otherwise, the body is read
+/// as a Neon error and returned as [`NeonError::Api`]. Error bodies which are
+/// not Neon errors, like the ones of a gateway, are kept as the message.
+async fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T, NeonError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response.json::<T>().await?);
+    }
+    let body = response.text().await?;
+    match serde_json::from_str::<model::NeonError>(&body) {
+        Ok(error) => Err(NeonError::Api {
+            code: Some(error.code),
+            message: error.message,
+            status,
+        }),
+        Err(_) => Err(NeonError::Api {
+            code: None,
+            message: body,
+            status,
+        }),
+    }
+}
+
+/// Convert an error retrieving a configuration value from AWS.
+fn aws_configuration_error(name: &str, e: AWSError) -> NeonError {
+    match e {
+        AWSError::SecretNotFound(_)
+        | AWSError::SecretKeyNotFound { .. }
+        | AWSError::ParameterNotFound(_) => NeonError::MissingConfiguration {
+            name: name.to_string(),
+            source: Box::new(e),
+        },
+        e => NeonError::Aws(e),
+    }
+}
+
+/// Options of a branch creation.
+#[derive(Debug, Default, Clone)]
+pub struct CreateBranchOptions {
+    /// Type of the compute endpoint created along with the branch.
+    pub endpoint_type: EndpointType,
+    /// The minimum number of Compute Units of the endpoint.
+    /// The minimum value is 0.25.
+    pub autoscaling_limit_min_cu: Option<f32>,
+    /// The maximum number of Compute Units of the endpoint.
+    pub autoscaling_limit_max_cu: Option<f32>,
+    /// Duration of inactivity in seconds after which the endpoint is
+    /// automatically suspended.
+    pub suspend_timeout_seconds: Option<u64>,
+}
+
+impl CreateBranchOptions {
+    /// Build the request creating a branch with these options.
+    fn request(&self, branch_name: &str) -> CreateBranchRequest {
+        CreateBranchRequest {
+            endpoints: vec![Endpoint {
+                r#type: self.endpoint_type,
+                autoscaling_limit_min_cu: self.autoscaling_limit_min_cu,
+                autoscaling_limit_max_cu: self.autoscaling_limit_max_cu,
+                suspend_timeout_seconds: self.suspend_timeout_seconds,
+                ..Default::default()
+            }],
+            branch: Branch {
+                name: Some(branch_name.into()),
+                ..Default::default()
+            },
+        }
+    }
 }
 
 pub struct Client {
     client: reqwest::Client,
     project_id: String,
+    projects_url: String,
 }
 
 impl Client {
     /// Create a new neon.tech REAT API client for a specific project.
     pub fn new(api_key: &str, project_id: &str) -> Result<Client, NeonError> {
+        Client::with_projects_url(api_key, project_id, NEON_PROJECTS_URL)
+    }
+
+    /// Create a new client from the `NEON_API_KEY` and `NEON_PROJECT_ID`
+    /// environment variables.
+    pub fn from_env() -> Result<Client, NeonError> {
+        let var = |name: &str| {
+            env::var(name).map_err(|e| NeonError::MissingConfiguration {
+                name: name.to_string(),
+                source: Box::new(e),
+            })
+        };
+        let api_key = var(NEON_API_KEY_VAR)?;
+        let project_id = var(NEON_PROJECT_ID_VAR)?;
+        Client::new(&api_key, &project_id)
+    }
+
+    /// Create a new client from the API key stored in the AWS secrets and the
+    /// project ID stored in the AWS parameters.
+    ///
+    /// Only a secret or a parameter which does not exist is reported as a
+    /// [`NeonError::MissingConfiguration`]. The other AWS errors, like
+    /// timeouts, are reported as [`NeonError::Aws`].
+    pub async fn from_aws() -> Result<Client, NeonError> {
+        let api_key = get_aws_secrets_value(NEON_API_KEY_SECRET, NEON_API_KEY_SECRET)
+            .await
+            .map_err(|e| aws_configuration_error(NEON_API_KEY_SECRET, e))?;
+        let project_id = get_aws_parameter_value(NEON_PROJECT_ID_PARAMETER)
+            .await
+            .map_err(|e| aws_configuration_error(NEON_PROJECT_ID_PARAMETER, e))?;
+        Client::new(&api_key, &project_id)
+    }
+
+    /// Create a new client querying the projects API at a specific URL.
+    pub fn with_projects_url(
+        api_key: &str,
+        project_id: &str,
+        projects_url: &str,
+    ) -> Result<Client, NeonError> {
+        Client::with_timeouts(api_key, project_id, projects_url, HttpTimeouts::default())
+    }
+
+    /// Create a new client querying the projects API at a specific URL, with
+    /// specific timeouts.
+    pub fn with_timeouts(
+        api_key: &str,
+        project_id: &str,
+        projects_url: &str,
+        timeouts: HttpTimeouts,
+    ) -> Result<Client, NeonError> {
         let mut headers = header::HeaderMap::new();
         let mut auth_value = HeaderValue::from_str(format!("Bearer {api_key}").as_ref())
             .map_err(|_| NeonError::InvalidAPIKey)?;
@@ -40,73 +230,821 @@ impl Client {
         headers.insert(header::AUTHORIZATION, auth_value);
         let client = reqwest::Client::builder()
             .default_headers(headers)
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.total)
             .build()?;
         Ok(Client {
             client,
             project_id: project_id.into(),
+            projects_url: projects_url.into(),
         })
     }
 
     /// Retrieves a list of branches for the specified project.
     ///
     /// Ref: https://api-docs.neon.tech/reference/listprojectbranches
-    pub async fn get_branches(&self) -> Result<ListBranchResponses, reqwest::Error> {
-        let neon_branches_url = format!("{}/{}/branches", NEON_PROJECTS_URL, self.project_id);
-        self.client
-            .get(&neon_branches_url)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<ListBranchResponses>()
+    pub async fn get_branches(&self) -> Result<ListBranchResponses, NeonError> {
+        self.get_branches_page(None, None).await
+    }
+
+    /// Retrieves a page of branches for the specified project.
+    ///
+    /// The `cursor` is the one returned in the pagination details of the
+    /// previous page, and `limit` is the maximum number of branches to return.
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/listprojectbranches
+    pub async fn get_branches_page(
+        &self,
+        cursor: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<ListBranchResponses, NeonError> {
+        let neon_branches_url = format!("{}/{}/branches", self.projects_url, self.project_id);
+        let mut request = self.client.get(&neon_branches_url);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        let response = request.send().await?;
+        parse_response::<ListBranchResponses>(response).await
+    }
+
+    /// Retrieves all the branches for the specified project, following the
+    /// pagination cursors until all the pages are exhausted.
+    pub async fn get_all_branches(&self) -> Result<ListBranchResponses, NeonError> {
+        let mut branches: Vec<ListBranchResponse> = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = self.get_branches_page(cursor.as_deref(), None).await?;
+            let next_cursor = page.pagination.and_then(|p| p.cursor);
+            let is_last_page =
+                page.branches.is_empty() || next_cursor.is_none() || next_cursor == cursor;
+            branches.extend(page.branches);
+            if is_last_page {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        Ok(ListBranchResponses {
+            branches,
+            pagination: None,
+        })
+    }
+
+    /// Finds a branch of the specified project by name.
+    pub async fn find_branch_by_name(&self, name: &str) -> Result<Option<Branch>, NeonError> {
+        let branches = self.get_all_branches().await?;
+        Ok(branches
+            .branches
+            .into_iter()
+            .map(|b| b.branch)
+            .find(|b| b.name.as_deref() == Some(name)))
+    }
+
+    /// Checks whether a branch with the specified name exists in the project.
+    pub async fn branch_name_exists(&self, name: &str) -> Result<bool, NeonError> {
+        Ok(self.find_branch_by_name(name).await?.is_some())
+    }
+
+    /// Retrieves information about the specified branch.
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/getprojectbranch
+    pub async fn get_branch(&self, branch_id: &str) -> Result<Branch, NeonError> {
+        let neon_branch_url = format!(
+            "{}/{}/branches/{}",
+            self.projects_url, self.project_id, branch_id
+        );
+        let response = self.client.get(&neon_branch_url).send().await?;
+        parse_response::<GetBranchResponse>(response)
+            .await
+            .map(|response| response.branch)
+    }
+
+    /// Retrieves the compute endpoints of the specified branch.
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/listprojectbranchendpoints
+    pub async fn get_branch_endpoints(&self, branch_id: &str) -> Result<Vec<Endpoint>, NeonError> {
+        let neon_endpoints_url = format!(
+            "{}/{}/branches/{}/endpoints",
+            self.projects_url, self.project_id, branch_id
+        );
+        let response = self.client.get(&neon_endpoints_url).send().await?;
+        parse_response::<ListEndpointsResponse>(response)
+            .await
+            .map(|response| response.endpoints)
+    }
+
+    /// Retrieves the databases of the specified branch.
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/listprojectbranchdatabases
+    pub async fn get_branch_databases(&self, branch_id: &str) -> Result<Vec<Database>, NeonError> {
+        let neon_databases_url = format!(
+            "{}/{}/branches/{}/databases",
+            self.projects_url, self.project_id, branch_id
+        );
+        let response = self.client.get(&neon_databases_url).send().await?;
+        parse_response::<ListDatabasesResponse>(response)
+            .await
+            .map(|response| response.databases)
+    }
+
+    /// Retrieves the roles of the specified branch.
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/listprojectbranchroles
+    pub async fn get_branch_roles(&self, branch_id: &str) -> Result<Vec<Role>, NeonError> {
+        let neon_roles_url = format!(
+            "{}/{}/branches/{}/roles",
+            self.projects_url, self.project_id, branch_id
+        );
+        let response = self.client.get(&neon_roles_url).send().await?;
+        parse_response::<ListRolesResponse>(response)
             .await
+            .map(|response| response.roles)
     }
 
-    /// Creates a branch in the specified project.
+    /// Creates a branch in the specified project, with a read-write compute
+    /// endpoint.
     ///
     /// Ref: https://api-docs.neon.tech/reference/createprojectbranch
     pub async fn create_branch(
         &self,
         branch_name: &str,
-    ) -> Result<CreateBranchResponse, reqwest::Error> {
-        let create_branch_request = CreateBranchRequest {
-            endpoints: vec![Endpoint {
-                r#type: EndpointType::ReadWrite,
-                ..Default::default()
-            }],
-            branch: Branch {
-                name: Some(branch_name.into()),
-                ..Default::default()
-            },
-        };
-        let neon_branches_url = format!("{}/{}/branches", NEON_PROJECTS_URL, self.project_id);
-        self.client
+    ) -> Result<CreateBranchResponse, NeonError> {
+        self.create_branch_with(branch_name, &CreateBranchOptions::default())
+            .await
+    }
+
+    /// Creates a branch in the specified project, with specific options.
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/createprojectbranch
+    pub async fn create_branch_with(
+        &self,
+        branch_name: &str,
+        options: &CreateBranchOptions,
+    ) -> Result<CreateBranchResponse, NeonError> {
+        let create_branch_request = options.request(branch_name);
+        let neon_branches_url = format!("{}/{}/branches", self.projects_url, self.project_id);
+        let response = self
+            .client
             .post(&neon_branches_url)
             .json(&create_branch_request)
             .send()
-            .await?
-            .error_for_status()?
-            .json::<CreateBranchResponse>()
-            .await
+            .await?;
+        parse_response::<CreateBranchResponse>(response).await
     }
 
     /// Deletes the specified branch from a project, and places
     /// all endpoints into an idle state, breaking existing client connections.
     ///
     /// Ref: https://api-docs.neon.tech/reference/deleteprojectbranch
-    pub async fn delete_branch(
-        &self,
-        branch_id: &str,
-    ) -> Result<DeleteBranchResponse, reqwest::Error> {
+    pub async fn delete_branch(&self, branch_id: &str) -> Result<DeleteBranchResponse, NeonError> {
         let neon_branches_url = format!(
             "{}/{}/branches/{}",
-            NEON_PROJECTS_URL, self.project_id, branch_id
+            self.projects_url, self.project_id, branch_id
         );
-        self.client
-            .delete(&neon_branches_url)
+        let response = self.client.delete(&neon_branches_url).send().await?;
+        parse_response::<DeleteBranchResponse>(response).await
+    }
+
+    /// Resets a branch to the latest state of its parent branch.
+    ///
+    /// The data written to the branch since it was created is discarded, which
+    /// is cheaper than deleting and recreating the branch. The returned
+    /// operations can be awaited with [`Client::wait_for_operations`].
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/restoreprojectbranch
+    pub async fn reset_branch(&self, branch_id: &str) -> Result<RestoreBranchResponse, NeonError> {
+        let branch = self.get_branch(branch_id).await?;
+        let source_branch_id = branch
+            .parent_id
+            .ok_or_else(|| NeonError::NoParentBranch(branch_id.into()))?;
+        let restore_branch_request = RestoreBranchRequest {
+            source_branch_id,
+            ..Default::default()
+        };
+        let neon_restore_url = format!(
+            "{}/{}/branches/{}/restore",
+            self.projects_url, self.project_id, branch_id
+        );
+        let response = self
+            .client
+            .post(&neon_restore_url)
+            .json(&restore_branch_request)
             .send()
-            .await?
-            .error_for_status()?
-            .json::<DeleteBranchResponse>()
+            .await?;
+        parse_response::<RestoreBranchResponse>(response).await
+    }
+
+    /// Retrieves the details of an operation.
+    ///
+    /// Ref: https://api-docs.neon.tech/reference/getprojectoperation
+    pub async fn get_operation(&self, operation_id: &str) -> Result<Operation, NeonError> {
+        let neon_operation_url = format!(
+            "{}/{}/operations/{}",
+            self.projects_url, self.project_id, operation_id
+        );
+        let response = self.client.get(&neon_operation_url).send().await?;
+        parse_response::<GetOperationResponse>(response)
+            .await
+            .map(|response| response.operation)
+    }
+
+    /// Waits until all the operations are finished.
+    ///
+    /// The operations are polled until they finish, fail, or until the timeout
+    /// elapses. The `retry_at` hint returned by the API is honored when present.
+    pub async fn wait_for_operations(
+        &self,
+        operations: &[Operation],
+        timeout: Duration,
+    ) -> Result<(), NeonError> {
+        let deadline = Instant::now() + timeout;
+        for operation_id in operations.iter().filter_map(|o| o.id.as_deref()) {
+            loop {
+                let operation = self.get_operation(operation_id).await?;
+                if operation.is_finished() {
+                    break;
+                }
+                if operation.is_failed() {
+                    return Err(NeonError::OperationFailed {
+                        id: operation_id.into(),
+                        status: operation.status.unwrap_or_default(),
+                    });
+                }
+
+                // Wait before polling again, without going past the deadline. A
+                // retry date in the past, which is common, still waits for the
+                // minimum interval to avoid flooding the API.
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(NeonError::Timeout(operation_id.into()));
+                }
+                let delay = match operation.retry_at {
+                    Some(retry_at) => (retry_at - OffsetDateTime::now_utc())
+                        .try_into()
+                        .unwrap_or(Duration::ZERO)
+                        .max(MIN_POLL_INTERVAL),
+                    None => OPERATION_POLL_INTERVAL,
+                };
+                tokio::time::sleep(delay.min(deadline - now)).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_json, header, method, path, query_param, query_param_is_missing},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    /// Serialize the tests modifying the environment variables.
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_client_from_env() {
+        let _lock = ENV_LOCK.lock().unwrap();
+        env::set_var(NEON_API_KEY_VAR, "api_key");
+        env::set_var(NEON_PROJECT_ID_VAR, "patient-smoke-782429");
+        let client = Client::from_env().unwrap();
+        assert_eq!(client.project_id, "patient-smoke-782429");
+
+        env::remove_var(NEON_PROJECT_ID_VAR);
+        let err = Client::from_env().err().unwrap();
+        assert!(
+            matches!(err, NeonError::MissingConfiguration { ref name, .. } if name == NEON_PROJECT_ID_VAR)
+        );
+
+        env::remove_var(NEON_API_KEY_VAR);
+        let err = Client::from_env().err().unwrap();
+        assert!(
+            matches!(err, NeonError::MissingConfiguration { ref name, .. } if name == NEON_API_KEY_VAR)
+        );
+    }
+
+    #[test]
+    fn test_aws_configuration_error() {
+        let err = aws_configuration_error(
+            NEON_PROJECT_ID_PARAMETER,
+            AWSError::ParameterNotFound(NEON_PROJECT_ID_PARAMETER.to_string()),
+        );
+        assert!(
+            matches!(err, NeonError::MissingConfiguration { ref name, .. } if name == NEON_PROJECT_ID_PARAMETER)
+        );
+
+        let err = aws_configuration_error(NEON_API_KEY_SECRET, AWSError::MissingSessionToken);
+        assert!(matches!(err, NeonError::Aws(AWSError::MissingSessionToken)));
+    }
+
+    fn operation_json(status: &str) -> String {
+        operation_json_with(status, "")
+    }
+
+    /// Serialize an operation, with some extra fields.
+    fn operation_json_with(status: &str, extra: &str) -> String {
+        format!(
+            r#"{{
+              "operation": {{
+                "id": "73fa0fb1-b96e-43ba-968f-571836a0ffbe",
+                "project_id": "patient-smoke-782429",
+                "branch_id": "br-odd-dream-88611736",
+                "action": "create_branch",
+                "status": "{status}",
+                "failures_count": 0,
+                "created_at": "2023-10-13T20:15:28Z",
+                "updated_at": "2023-10-13T20:15:28Z",
+                "total_duration_ms": 0{extra}
+              }}
+            }}"#
+        )
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/patient-smoke-782429/branches/br-odd-dream-88611736"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let timeouts = HttpTimeouts {
+            connect: Duration::from_millis(100),
+            total: Duration::from_millis(200),
+        };
+        let client =
+            Client::with_timeouts("key", "patient-smoke-782429", &server.uri(), timeouts).unwrap();
+        let start = Instant::now();
+        let err = client
+            .get_branch("br-odd-dream-88611736")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeonError::Reqwest(ref e) if e.is_timeout()));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_operations() {
+        let server = MockServer::start().await;
+        let operation_path =
+            "/patient-smoke-782429/operations/73fa0fb1-b96e-43ba-968f-571836a0ffbe";
+        Mock::given(method("GET"))
+            .and(path(operation_path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(operation_json("running"), "application/json"),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(operation_path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(operation_json("finished"), "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let operations = vec![Operation {
+            id: Some("73fa0fb1-b96e-43ba-968f-571836a0ffbe".into()),
+            status: Some("running".into()),
+            ..Default::default()
+        }];
+        client
+            .wait_for_operations(&operations, Duration::from_secs(10))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_operations_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(operation_json("running"), "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let operations = vec![Operation {
+            id: Some("73fa0fb1-b96e-43ba-968f-571836a0ffbe".into()),
+            ..Default::default()
+        }];
+        let err = client
+            .wait_for_operations(&operations, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeonError::Timeout(_)));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_operations_polls_at_deadline() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(operation_json("running"), "application/json"),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(operation_json("finished"), "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        // The deadline comes before the next poll, which must still happen.
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let operations = vec![Operation {
+            id: Some("73fa0fb1-b96e-43ba-968f-571836a0ffbe".into()),
+            ..Default::default()
+        }];
+        client
+            .wait_for_operations(&operations, Duration::from_millis(100))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_operations_retry_at_in_the_past() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                operation_json_with("running", r#", "retry_at": "2023-10-13T20:15:29Z""#),
+                "application/json",
+            ))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(operation_json("finished"), "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let operations = vec![Operation {
+            id: Some("73fa0fb1-b96e-43ba-968f-571836a0ffbe".into()),
+            ..Default::default()
+        }];
+        let start = Instant::now();
+        client
+            .wait_for_operations(&operations, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= MIN_POLL_INTERVAL);
+        assert!(start.elapsed() < OPERATION_POLL_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_operations_stale_retry_at_is_throttled() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                operation_json_with("running", r#", "retry_at": "2023-10-13T20:15:29Z""#),
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let operations = vec![Operation {
+            id: Some("73fa0fb1-b96e-43ba-968f-571836a0ffbe".into()),
+            ..Default::default()
+        }];
+        let timeout = Duration::from_millis(500);
+        let err = client
+            .wait_for_operations(&operations, timeout)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeonError::Timeout(_)));
+
+        // One poll per interval, plus the first one and the one at the deadline.
+        let polls = server.received_requests().await.unwrap().len() as u32;
+        assert!(polls <= timeout.as_millis() as u32 / MIN_POLL_INTERVAL.as_millis() as u32 + 2);
+    }
+
+    #[tokio::test]
+    async fn test_api_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/patient-smoke-782429/branches"))
+            .respond_with(ResponseTemplate::new(422).set_body_raw(
+                r#"{"code": "BRANCHES_LIMIT_EXCEEDED", "message": "branches limit exceeded"}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let err = client.create_branch("help-me-debug").await.unwrap_err();
+        match err {
+            NeonError::Api {
+                code,
+                message,
+                status,
+            } => {
+                assert_eq!(code.as_deref(), Some("BRANCHES_LIMIT_EXCEEDED"));
+                assert_eq!(message, "branches limit exceeded");
+                assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            }
+            _ => panic!("expected a NeonError::Api, got {err:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_error_plain_text() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/patient-smoke-782429/branches/br-odd-dream-88611736"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("404 page not found"))
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let err = client
+            .delete_branch("br-odd-dream-88611736")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Neon API error (404 Not Found): 404 page not found"
+        );
+        match err {
+            NeonError::Api {
+                code,
+                message,
+                status,
+            } => {
+                assert_eq!(code, None);
+                assert_eq!(message, "404 page not found");
+                assert_eq!(status, StatusCode::NOT_FOUND);
+            }
+            _ => panic!("expected a NeonError::Api, got {err:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_branches() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/patient-smoke-782429/branches"))
+            .and(header("authorization", "Bearer api_key"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{
+                  "branches": [
+                    {"id": "br-round-pine-192368", "name": "main"},
+                    {"id": "br-still-breeze-64375152", "name": "remy-is-testing"}
+                  ]
+                }"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let branches = client.get_branches().await.unwrap();
+        let names = branches
+            .branches
+            .iter()
+            .filter_map(|b| b.branch.name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "remy-is-testing"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_all_branches() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/patient-smoke-782429/branches"))
+            .and(query_param_is_missing("cursor"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{
+                  "branches": [
+                    {"id": "br-round-pine-192368", "name": "main"},
+                    {"id": "br-still-breeze-64375152", "name": "remy-is-testing"}
+                  ],
+                  "pagination": {"cursor": "remy-is-testing"}
+                }"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/patient-smoke-782429/branches"))
+            .and(query_param("cursor", "remy-is-testing"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{
+                  "branches": [
+                    {"id": "br-soft-star-53161169", "name": "usa-santa-rosa-new-mexico"}
+                  ]
+                }"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let branches = client.get_all_branches().await.unwrap();
+        assert_eq!(branches.branches.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_branch_endpoints() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/patient-smoke-782429/branches/br-odd-dream-88611736/endpoints",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{
+                  "endpoints": [
+                    {
+                      "id": "ep-small-frog-123456",
+                      "branch_id": "br-odd-dream-88611736",
+                      "host": "ep-small-frog-123456.us-west-2.aws.neon.tech",
+                      "type": "read_write"
+                    }
+                  ]
+                }"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let endpoints = client
+            .get_branch_endpoints("br-odd-dream-88611736")
+            .await
+            .unwrap();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(
+            endpoints[0].host.as_deref(),
+            Some("ep-small-frog-123456.us-west-2.aws.neon.tech")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_branch_databases_and_roles() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/patient-smoke-782429/branches/br-odd-dream-88611736/databases",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{
+                  "databases": [
+                    {
+                      "id": 834686,
+                      "branch_id": "br-odd-dream-88611736",
+                      "name": "neondb",
+                      "owner_name": "bna"
+                    }
+                  ]
+                }"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/patient-smoke-782429/branches/br-odd-dream-88611736/roles",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{
+                  "roles": [
+                    {
+                      "branch_id": "br-odd-dream-88611736",
+                      "name": "bna",
+                      "protected": false
+                    }
+                  ]
+                }"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let databases = client
+            .get_branch_databases("br-odd-dream-88611736")
+            .await
+            .unwrap();
+        assert_eq!(databases.len(), 1);
+        assert_eq!(databases[0].name.as_deref(), Some("neondb"));
+        let roles = client
+            .get_branch_roles("br-odd-dream-88611736")
+            .await
+            .unwrap();
+        assert_eq!(roles.len(), 1);
+        assert_eq!(roles[0].name.as_deref(), Some("bna"));
+    }
+
+    #[tokio::test]
+    async fn test_reset_branch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/patient-smoke-782429/branches/br-still-breeze-64375152"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"branch": {"id": "br-still-breeze-64375152", "parent_id": "br-round-pine-192368"}}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(
+                "/patient-smoke-782429/branches/br-still-breeze-64375152/restore",
+            ))
+            .and(body_json(serde_json::json!({
+                "source_branch_id": "br-round-pine-192368"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"branch": {"id": "br-still-breeze-64375152"}, "operations": []}"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client =
+            Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri()).unwrap();
+        let response = client
+            .reset_branch("br-still-breeze-64375152")
             .await
+            .unwrap();
+        assert!(response.operations.is_empty());
+    }
+
+    #[test]
+    fn test_create_branch_request_endpoint_type() {
+        let request = CreateBranchOptions::default().request("usa-santa-rosa-new-mexico");
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["endpoints"][0]["type"], "read_write");
+        assert_eq!(json["branch"]["name"], "usa-santa-rosa-new-mexico");
+
+        let options = CreateBranchOptions {
+            endpoint_type: EndpointType::ReadOnly,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(options.request("usa-santa-rosa-new-mexico")).unwrap();
+        assert_eq!(json["endpoints"][0]["type"], "read_only");
+    }
+
+    #[test]
+    fn test_create_branch_request_autoscaling() {
+        let json = serde_json::to_value(CreateBranchOptions::default().request("main")).unwrap();
+        let endpoint = json["endpoints"][0].as_object().unwrap();
+        assert!(!endpoint.contains_key("autoscaling_limit_min_cu"));
+        assert!(!endpoint.contains_key("autoscaling_limit_max_cu"));
+        assert!(!endpoint.contains_key("suspend_timeout_seconds"));
+
+        let options = CreateBranchOptions {
+            autoscaling_limit_min_cu: Some(0.25),
+            autoscaling_limit_max_cu: Some(1.0),
+            suspend_timeout_seconds: Some(60),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(options.request("main")).unwrap();
+        assert_eq!(
+            json["endpoints"][0],
+            serde_json::json!({
+                "type": "read_write",
+                "autoscaling_limit_min_cu": 0.25,
+                "autoscaling_limit_max_cu": 1.0,
+                "suspend_timeout_seconds": 60
+            })
+        );
     }
 }
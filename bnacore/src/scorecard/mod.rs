@@ -1,12 +1,41 @@
+//! Scorecard formats used by the BNA.
+//!
+//! Every scorecard format implements the traits of this module:
+//! - [`ScorecardCsv`] to read and write the scorecards from/to CSV files,
+//! - [`ScorecardJson`] to read and write the scorecards from/to JSON files,
+//! - [`Scorecard`] to access the information common to all the formats.
+//!
+//! [`ScoreCardVersion`] wraps the scorecard formats and dispatches the
+//! [`Scorecard`] calls to the wrapped version.
+//!
+//! ```
+//! use bnacore::scorecard::{
+//!     scorecard24::ScoreCard24, ScoreCardVersion, Scorecard, ScorecardCsv,
+//! };
+//!
+//! fn is_scorecard<T: Scorecard + ScorecardCsv>() {}
+//! is_scorecard::<ScoreCard24>();
+//!
+//! fn full_name(scorecard: &ScoreCardVersion) -> String {
+//!     scorecard.full_name()
+//! }
+//! ```
+pub mod overall;
 pub mod scorecard21;
 pub mod scorecard23;
 pub mod scorecard24;
 pub mod shortscorecard;
 
-use crate::{Dataset, Error};
+use crate::{versioning::Calver, Dataset, Error};
 use csv::Reader;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::path::Path;
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    str::FromStr,
+};
 use url::Url;
 
 use self::{
@@ -21,13 +50,22 @@ pub trait ScorecardCsv {
         P: AsRef<Path>,
         Self: Sized + DeserializeOwned,
     {
-        let mut csv_reader = Reader::from_path(path)?;
-        let mut scorecards: Vec<Self> = vec![];
-        for record in csv_reader.deserialize() {
-            scorecards.push(record?);
-        }
+        Self::from_csv_iter(path)?.collect()
+    }
 
-        Ok(scorecards)
+    /// Read a CSV file and yield the records one at a time.
+    ///
+    /// Unlike [`ScorecardCsv::from_csv`], the records are not all loaded in
+    /// memory at once.
+    fn from_csv_iter<P>(path: P) -> Result<impl Iterator<Item = Result<Self, Error>>, Error>
+    where
+        P: AsRef<Path>,
+        Self: Sized + DeserializeOwned,
+    {
+        let csv_reader = Reader::from_path(path)?;
+        Ok(csv_reader
+            .into_deserialize()
+            .map(|record| record.map_err(Error::from)))
     }
 
     /// Saves a slice of Ts to a CSV file.
@@ -44,6 +82,28 @@ pub trait ScorecardCsv {
     }
 }
 
+pub trait ScorecardJson {
+    /// Read a JSON file containing an array of entries and populate a Vector of Self.
+    fn from_json<P>(path: P) -> Result<Vec<Self>, Error>
+    where
+        P: AsRef<Path>,
+        Self: Sized + DeserializeOwned,
+    {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Saves a slice of Ts to a JSON file, as an array.
+    fn to_json<P, T>(path: P, entries: &[T]) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+        T: Serialize,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer_pretty(writer, entries)?)
+    }
+}
+
 pub trait Scorecard {
     /// Return the full name of the city.
     ///
@@ -53,8 +113,18 @@ pub trait Scorecard {
     /// Return the URL of the specified dataset.
     fn url(&self, dataset: &Dataset) -> Result<Url, Error>;
 
+    /// Return the URLs of the specified datasets, paired with their dataset.
+    ///
+    /// Stop at the first URL which cannot be built.
+    fn dataset_urls(&self, datasets: &[Dataset]) -> Result<Vec<(Dataset, Url)>, Error> {
+        datasets
+            .iter()
+            .map(|dataset| Ok((*dataset, self.url(dataset)?)))
+            .collect()
+    }
+
     /// Return the envtry version in calver (Ubuntu).
-    fn version(&self) -> String;
+    fn version(&self) -> Result<String, Error>;
 }
 
 #[derive(Debug, Clone)]
@@ -88,7 +158,7 @@ impl Scorecard for ScoreCardVersion {
         }
     }
 
-    fn version(&self) -> String {
+    fn version(&self) -> Result<String, Error> {
         match self {
             ScoreCardVersion::V21(s) => s.version(),
             ScoreCardVersion::V23(s) => s.version(),
@@ -97,6 +167,71 @@ impl Scorecard for ScoreCardVersion {
     }
 }
 
+impl ScoreCardVersion {
+    /// Return the size category of the city, if the format provides it.
+    pub fn pop_size(&self) -> Option<&Size> {
+        match self {
+            ScoreCardVersion::V21(_) => None,
+            ScoreCardVersion::V23(s) => Some(&s.bna.pop_size),
+            ScoreCardVersion::V24(s) => s.pop_size.as_ref(),
+        }
+    }
+
+    /// Return the version of the scorecard, parsed as a [`Calver`].
+    pub fn calver(&self) -> Result<Calver, Error> {
+        let version = self.version()?;
+        Calver::try_from_ubuntu(&version)
+            .map_err(|e| Error::InvalidArgument(format!("invalid version {version}: {e}")))
+    }
+
+    /// Return the region of the city, if the format provides it.
+    pub fn region(&self) -> Option<&str> {
+        match self {
+            ScoreCardVersion::V21(_) => None,
+            ScoreCardVersion::V23(s) => Some(&s.city.region),
+            ScoreCardVersion::V24(s) => Some(&s.region),
+        }
+    }
+
+    /// Return `true` if the scorecard matches a size category and a region.
+    ///
+    /// See [`filter_scorecards`] for the matching rules.
+    pub fn matches(&self, pop_size: Option<&Size>, region: Option<&str>) -> bool {
+        pop_size.is_none_or(|size| self.pop_size() == Some(size))
+            && region.is_none_or(|region| {
+                self.region()
+                    .is_some_and(|r| r.eq_ignore_ascii_case(region))
+            })
+    }
+}
+
+/// Return the newest scorecard, comparing their parsed versions.
+///
+/// The scorecards with an invalid version are ignored.
+pub fn latest(scorecards: &[ScoreCardVersion]) -> Option<&ScoreCardVersion> {
+    scorecards
+        .iter()
+        .filter_map(|s| s.calver().ok().map(|version| (version, s)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, s)| s)
+}
+
+/// Keep only the scorecards matching a size category and a region.
+///
+/// A `None` criteria matches all the scorecards. The regions are compared
+/// without regard to case. The scorecards without size category or region,
+/// like the v21 ones, never match a criteria.
+pub fn filter_scorecards(
+    scorecards: Vec<ScoreCardVersion>,
+    pop_size: Option<&Size>,
+    region: Option<&str>,
+) -> Vec<ScoreCardVersion> {
+    scorecards
+        .into_iter()
+        .filter(|s| s.matches(pop_size, region))
+        .collect()
+}
+
 impl From<&ScoreCardVersion> for ShortScoreCard {
     fn from(value: &ScoreCardVersion) -> Self {
         match value {
@@ -107,7 +242,10 @@ impl From<&ScoreCardVersion> for ShortScoreCard {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Size category of a city.
+///
+/// The sizes are ordered from the smallest to the largest.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
 pub enum Size {
     /// Represent small cities.
@@ -117,3 +255,231 @@ pub enum Size {
     /// Represent large cities.
     Large,
 }
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Size::Small => write!(f, "small"),
+            Size::Medium => write!(f, "medium"),
+            Size::Large => write!(f, "large"),
+        }
+    }
+}
+
+impl FromStr for Size {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "small" => Ok(Size::Small),
+            "medium" => Ok(Size::Medium),
+            "large" => Ok(Size::Large),
+            _ => Err(Error::InvalidArgument(format!(
+                "invalid size `{s}`: expected small, medium or large"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scorecard_version_v24() {
+        let raw_json = r#"
+          {
+            "city": "Christchurch",
+            "state": "CAN",
+            "state_full": "Canterbury",
+            "country": "New Zealand",
+            "region": "New Zealand",
+            "year": 2023,
+            "census_fips_code": 9900246,
+            "census_population": 389300,
+            "census_latitude": -43.532,
+            "census_longitude": 172.6306,
+            "residential_speed_limit": 25,
+            "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+            "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+            "bna_rounded_score": 53,
+            "creation_date": "2023-04-17T16:31:00Z",
+            "filename": "_Christchurch_v23.1.csv"
+          }"#;
+        let scorecard =
+            ScoreCardVersion::V24(serde_json::from_str::<ScoreCard24>(raw_json).unwrap());
+        assert_eq!(scorecard.full_name(), "New Zealand-Canterbury-Christchurch");
+        assert_eq!(
+            scorecard.url(&Dataset::Ways).unwrap().as_str(),
+            "https://s3.amazonaws.com/production-pfb-storage-us-east-1/results/9ac2465b-04f7-48a0-adc6-92502243b6e3/neighborhood_ways.zip"
+        );
+        assert_eq!(scorecard.version().unwrap(), "23.1");
+    }
+
+    #[test]
+    fn test_size_round_trip() {
+        for size in [Size::Small, Size::Medium, Size::Large] {
+            assert_eq!(size.to_string().parse::<Size>().unwrap(), size);
+            assert_eq!(serde_json::to_string(&size).unwrap(), format!("\"{size}\""));
+        }
+        assert!(matches!(
+            "huge".parse::<Size>(),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_size_ordering() {
+        assert!(Size::Small < Size::Medium);
+        assert!(Size::Medium < Size::Large);
+        let mut sizes = vec![Size::Large, Size::Small, Size::Medium];
+        sizes.sort();
+        assert_eq!(sizes, vec![Size::Small, Size::Medium, Size::Large]);
+    }
+
+    #[test]
+    fn test_latest() {
+        let scorecard = |filename: &str| {
+            let raw_json = format!(
+                r#"{{
+                  "city": "Christchurch",
+                  "state": "CAN",
+                  "state_full": "Canterbury",
+                  "country": "New Zealand",
+                  "region": "New Zealand",
+                  "year": 2023,
+                  "census_population": 389300,
+                  "census_latitude": -43.532,
+                  "census_longitude": 172.6306,
+                  "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+                  "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+                  "bna_rounded_score": 53,
+                  "creation_date": "2023-04-17T16:31:00Z",
+                  "filename": "{filename}"
+                }}"#
+            );
+            ScoreCardVersion::V24(serde_json::from_str::<ScoreCard24>(&raw_json).unwrap())
+        };
+        let scorecards = vec![
+            scorecard("_Christchurch_v9.12.csv"),
+            scorecard("_Christchurch_v24.05.2.csv"),
+            scorecard("_Christchurch.csv"),
+            scorecard("_Christchurch_v24.05.10.csv"),
+            scorecard("_Christchurch_v23.1.csv"),
+        ];
+        assert_eq!(latest(&scorecards).unwrap().version().unwrap(), "24.05.10");
+
+        // The scorecards with an invalid version are ignored.
+        assert!(latest(&scorecards[2..3]).is_none());
+        assert!(latest(&[]).is_none());
+    }
+
+    #[test]
+    fn test_filter_scorecards() {
+        let scorecard = |city: &str, region: &str, pop_size: &str| {
+            let raw_json = format!(
+                r#"{{
+                  "city": "{city}",
+                  "state": null,
+                  "state_full": "{region}",
+                  "country": "United States",
+                  "region": "{region}",
+                  "year": 2024,
+                  "census_population": 100000,
+                  "census_latitude": 0.0,
+                  "census_longitude": 0.0,
+                  "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+                  "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+                  "bna_rounded_score": 53,
+                  "pop_size": "{pop_size}",
+                  "creation_date": "2024-04-17T16:31:00Z",
+                  "filename": "{city}.csv"
+                }}"#
+            );
+            ScoreCardVersion::V24(serde_json::from_str::<ScoreCard24>(&raw_json).unwrap())
+        };
+        let scorecards = vec![
+            scorecard("Austin", "South", "large"),
+            scorecard("Boulder", "Mountain", "small"),
+            scorecard("Denver", "Mountain", "large"),
+        ];
+        let cities = |scorecards: Vec<ScoreCardVersion>| {
+            scorecards
+                .iter()
+                .map(|s| match s {
+                    ScoreCardVersion::V24(s) => s.city.clone(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            cities(filter_scorecards(scorecards.clone(), None, None)),
+            ["Austin", "Boulder", "Denver"]
+        );
+        assert_eq!(
+            cities(filter_scorecards(
+                scorecards.clone(),
+                Some(&Size::Large),
+                None
+            )),
+            ["Austin", "Denver"]
+        );
+        assert_eq!(
+            cities(filter_scorecards(
+                scorecards.clone(),
+                None,
+                Some("mountain")
+            )),
+            ["Boulder", "Denver"]
+        );
+        assert_eq!(
+            cities(filter_scorecards(
+                scorecards,
+                Some(&Size::Small),
+                Some("Mountain")
+            )),
+            ["Boulder"]
+        );
+    }
+
+    #[test]
+    fn test_from_csv_iter() {
+        let raw_json = r#"
+          {
+            "city": "Christchurch",
+            "state": "CAN",
+            "state_full": "Canterbury",
+            "country": "New Zealand",
+            "region": "New Zealand",
+            "year": 2023,
+            "census_fips_code": 9900246,
+            "census_population": 389300,
+            "census_latitude": -43.532,
+            "census_longitude": 172.6306,
+            "residential_speed_limit": 25,
+            "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+            "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+            "bna_rounded_score": 53,
+            "creation_date": "2023-04-17T16:31:00Z",
+            "filename": "_Christchurch_v23.1.csv"
+          }"#;
+        let christchurch = serde_json::from_str::<ScoreCard24>(raw_json).unwrap();
+        let mut wellington = christchurch.clone();
+        wellington.city = "Wellington".to_string();
+        let dir = tempfile::tempdir().unwrap();
+        let csv = dir.path().join("city_ratings.csv");
+        ScoreCard24::to_csv(&csv, &[christchurch, wellington]).unwrap();
+
+        let eager = ScoreCard24::from_csv(&csv).unwrap();
+        let lazy = ScoreCard24::from_csv_iter(&csv)
+            .unwrap()
+            .collect::<Result<Vec<ScoreCard24>, Error>>()
+            .unwrap();
+        assert_eq!(lazy.len(), 2);
+        assert_eq!(
+            serde_json::to_value(&lazy).unwrap(),
+            serde_json::to_value(&eager).unwrap()
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Overall scores computed by the Brokenspoke analyzer.
+//!
+//! The analyzer writes its results to a `neighborhood_overall_scores.csv`
+//! file, one row per score id. [`OverallScores`] reads this file and converts
+//! it to a [`BNAPost`], the payload expected by the BNA API.
+use crate::Error;
+use csv::ReaderBuilder;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io::Read};
+use uuid::Uuid;
+
+/// Number of rows in a complete overall scores file.
+pub const OVERALL_SCORES_COUNT: usize = 23;
+
+/// Score ids which must be present for the results to be meaningful.
+pub const REQUIRED_SCORE_IDS: [&str; 7] = [
+    "overall_score",
+    "people",
+    "opportunity",
+    "core_services",
+    "retail",
+    "recreation",
+    "transit",
+];
+
+/// Represent a row of the overall scores file.
+#[derive(Debug, Deserialize, Clone)]
+pub struct OverallScore {
+    pub score_id: String,
+    pub score_original: Option<f64>,
+    pub score_normalized: Option<f64>,
+}
+
+/// Overall scores, indexed by score id.
+#[derive(Debug, Deserialize)]
+pub struct OverallScores(HashMap<String, OverallScore>);
+
+impl OverallScores {
+    /// Create an empty OverallScores.
+    pub fn new() -> Self {
+        OverallScores(HashMap::with_capacity(OVERALL_SCORES_COUNT))
+    }
+
+    /// Read the overall scores from CSV data.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut rdr = ReaderBuilder::new().flexible(true).from_reader(reader);
+        let scores = rdr
+            .deserialize()
+            .collect::<Result<Vec<OverallScore>, csv::Error>>()?;
+        Ok(OverallScores::from(scores))
+    }
+
+    /// Retrieve an OverallScore item by id.
+    pub fn get_overall_score(&self, score_id: &str) -> Option<&OverallScore> {
+        self.0.get(score_id)
+    }
+
+    /// Retrieve the normalized score of an OverallScore item by id.
+    pub fn get_normalized_score(&self, score_id: &str) -> Option<f64> {
+        self.get_overall_score(score_id)
+            .and_then(|s| s.score_normalized)
+    }
+
+    /// Retrieve the original score of an OverallScore item by id.
+    pub fn get_original_score(&self, score_id: &str) -> Option<f64> {
+        self.get_overall_score(score_id)
+            .and_then(|s| s.score_original)
+    }
+
+    /// Retrieve the total population of the analyzed area.
+    pub fn population(&self) -> Option<u32> {
+        self.get_original_score("population_total")
+            .map(|population| population.round() as u32)
+    }
+
+    /// Return the ids from `required` which are not present in the scores.
+    pub fn missing(&self, required: &[&str]) -> Vec<String> {
+        required
+            .iter()
+            .filter(|score_id| !self.0.contains_key(**score_id))
+            .map(|score_id| score_id.to_string())
+            .collect()
+    }
+
+    /// Convert the overall scores to a new BNA entry for the specified city.
+    pub fn to_bna_post(&self, version: String, city_id: Uuid) -> BNAPost {
+        BNAPost {
+            core_services: BNACoreServices {
+                dentists: self.get_normalized_score("core_services_dentists"),
+                doctors: self.get_normalized_score("core_services_doctors"),
+                grocery: self.get_normalized_score("core_services_grocery"),
+                hospitals: self.get_normalized_score("core_services_hospitals"),
+                pharmacies: self.get_normalized_score("core_services_pharmacies"),
+                social_services: self.get_normalized_score("core_services_social_services"),
+                score: self
+                    .get_normalized_score("core_services")
+                    .unwrap_or_default(),
+            },
+            people: BNAPeople {
+                score: self.get_normalized_score("people"),
+            },
+            retail: BNARetail {
+                score: self.get_normalized_score("retail"),
+            },
+            transit: BNATransit {
+                score: self.get_normalized_score("transit"),
+            },
+            infrastructure: BNAInfrastructure {
+                low_stress_miles: self.get_normalized_score("total_miles_low_stress"),
+                high_stress_miles: self.get_normalized_score("total_miles_high_stress"),
+            },
+            opportunity: BNAOpportunity {
+                employment: self.get_normalized_score("opportunity_employment"),
+                higher_education: self.get_normalized_score("opportunity_higher_education"),
+                k12_education: self.get_normalized_score("opportunity_k12_education"),
+                technical_vocational_college: self
+                    .get_normalized_score("opportunity_technical_vocational_college"),
+                score: self.get_normalized_score("opportunity").unwrap_or_default(),
+            },
+            recreation: BNARecreation {
+                community_centers: self.get_normalized_score("recreation_community_centers"),
+                parks: self.get_normalized_score("recreation_parks"),
+                recreation_trails: self.get_normalized_score("recreation_trails"),
+                score: self.get_normalized_score("recreation").unwrap_or_default(),
+            },
+            summary: BNASummary {
+                bna_uuid: Uuid::new_v4(),
+                version,
+                city_id,
+                score: self
+                    .get_normalized_score("overall_score")
+                    .unwrap_or_default(),
+            },
+        }
+    }
+}
+
+impl Default for OverallScores {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Vec<OverallScore>> for OverallScores {
+    fn from(scores: Vec<OverallScore>) -> Self {
+        let mut overall_scores = OverallScores::new();
+        for score in scores {
+            overall_scores.0.insert(score.score_id.clone(), score);
+        }
+        overall_scores
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNASummary {
+    pub bna_uuid: Uuid,
+    pub version: String,
+    pub city_id: Uuid,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAInfrastructure {
+    pub low_stress_miles: Option<f64>,
+    pub high_stress_miles: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNARecreation {
+    pub community_centers: Option<f64>,
+    pub parks: Option<f64>,
+    pub recreation_trails: Option<f64>,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAOpportunity {
+    pub employment: Option<f64>,
+    pub higher_education: Option<f64>,
+    pub k12_education: Option<f64>,
+    pub technical_vocational_college: Option<f64>,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNACoreServices {
+    pub dentists: Option<f64>,
+    pub doctors: Option<f64>,
+    pub grocery: Option<f64>,
+    pub hospitals: Option<f64>,
+    pub pharmacies: Option<f64>,
+    pub social_services: Option<f64>,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAPeople {
+    pub score: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNARetail {
+    pub score: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNATransit {
+    pub score: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAPost {
+    pub core_services: BNACoreServices,
+    pub people: BNAPeople,
+    pub retail: BNARetail,
+    pub transit: BNATransit,
+    pub infrastructure: BNAInfrastructure,
+    pub opportunity: BNAOpportunity,
+    pub recreation: BNARecreation,
+    pub summary: BNASummary,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OVERALL_SCORES: &str = r#"id,score_id,score_original,score_normalized,human_explanation
+1,people,0.1917,19.1700,"On average, census blocks in the neighborhood received this population score."
+2,opportunity_employment,0.0826,8.2600,"On average, census blocks in the neighborhood received this employment score."
+3,opportunity_k12_education,0.0831,8.3100,"On average, census blocks in the neighborhood received this K12 schools score."
+4,opportunity_technical_vocational_college,0.0000,0.0000,"On average, census blocks in the neighborhood received this tech/vocational colleges score."
+5,opportunity_higher_education,0.0000,0.0000,"On average, census blocks in the neighborhood received this universities score."
+6,opportunity,0.0829,8.2900,
+7,core_services_doctors,0.0000,0.0000,"On average, census blocks in the neighborhood received this doctors score."
+8,core_services_dentists,0.0000,0.0000,"On average, census blocks in the neighborhood received this dentists score."
+9,core_services_hospitals,0.0518,5.1800,"On average, census blocks in the neighborhood received this hospital score."
+10,core_services_pharmacies,0.0000,0.0000,"On average, census blocks in the neighborhood received this pharmacies score."
+11,core_services_grocery,0.0169,1.6900,"On average, census blocks in the neighborhood received this grocery score."
+12,core_services_social_services,0.0000,0.0000,"On average, census blocks in the neighborhood received this social services score."
+13,core_services,0.0324,3.2400,
+14,retail,0.0000,0.0000,"On average, census blocks in the neighborhood received this retail score."
+15,recreation_parks,0.0713,7.1300,"On average, census blocks in the neighborhood received this parks score."
+16,recreation_trails,0.0000,0.0000,"On average, census blocks in the neighborhood received this trails score."
+17,recreation_community_centers,0.0000,0.0000,"On average, census blocks in the neighborhood received this community centers score."
+18,recreation,0.0713,7.1300,
+19,transit,0.0000,0.0000,"On average, census blocks in the neighborhood received this transit score."
+20,overall_score,0.0893,8.9300,
+21,population_total,2960.0000,,Total population of boundary
+22,total_miles_low_stress,9.3090,9.3000,Total low-stress miles
+23,total_miles_high_stress,64.5092,64.5000,Total high-stress miles"#;
+
+    #[test]
+    fn test_to_bna_post() {
+        let scores = OverallScores::from_reader(OVERALL_SCORES.as_bytes()).unwrap();
+        assert_eq!(scores.0.len(), OVERALL_SCORES_COUNT);
+        assert!(scores.missing(&REQUIRED_SCORE_IDS).is_empty());
+
+        let city_id = Uuid::new_v4();
+        let bna_post = scores.to_bna_post("24.05".to_string(), city_id);
+        assert_eq!(bna_post.summary.version, "24.05");
+        assert_eq!(bna_post.summary.city_id, city_id);
+        assert_eq!(bna_post.summary.score, 8.93);
+        assert_eq!(bna_post.people.score, Some(19.17));
+        assert_eq!(bna_post.retail.score, Some(0.0));
+        assert_eq!(bna_post.transit.score, Some(0.0));
+        assert_eq!(bna_post.core_services.score, 3.24);
+        assert_eq!(bna_post.core_services.hospitals, Some(5.18));
+        assert_eq!(bna_post.core_services.grocery, Some(1.69));
+        assert_eq!(bna_post.opportunity.score, 8.29);
+        assert_eq!(bna_post.opportunity.employment, Some(8.26));
+        assert_eq!(bna_post.opportunity.k12_education, Some(8.31));
+        assert_eq!(bna_post.recreation.score, 7.13);
+        assert_eq!(bna_post.recreation.recreation_trails, Some(0.0));
+        assert_eq!(bna_post.recreation.parks, Some(7.13));
+        assert_eq!(bna_post.infrastructure.low_stress_miles, Some(9.3));
+        assert_eq!(bna_post.infrastructure.high_stress_miles, Some(64.5));
+        assert_eq!(scores.population(), Some(2960));
+    }
+
+    #[test]
+    fn test_missing() {
+        let data = r#"id,score_id,score_original,score_normalized,human_explanation
+1,people,0.1917,19.1700,"On average, census blocks in the neighborhood received this population score."
+6,opportunity,0.0829,8.2900,
+13,core_services,0.0324,3.2400,
+14,retail,0.0000,0.0000,"On average, census blocks in the neighborhood received this retail score."
+18,recreation,0.0713,7.1300,
+19,transit,0.0000,0.0000,"On average, census blocks in the neighborhood received this transit score.""#;
+        let scores = OverallScores::from_reader(data.as_bytes()).unwrap();
+        assert_eq!(
+            scores.missing(&REQUIRED_SCORE_IDS),
+            vec!["overall_score".to_string()]
+        );
+    }
+}
@@ -14,7 +14,7 @@ use crate::{Dataset, Error, PFB_S3_PUBLIC_DOCUMENTS, PFB_S3_STORAGE_BASE_URL};
 use serde::Deserialize;
 use url::Url;
 
-use super::{Scorecard, ScorecardCsv};
+use super::{Scorecard, ScorecardCsv, ScorecardJson};
 
 /// Represent a PeopleForBikes city.
 #[derive(Debug, Deserialize, Clone)]
@@ -99,8 +99,8 @@ impl Scorecard for ScoreCard21 {
         Ok(Url::parse(&dataset_url)?)
     }
 
-    fn version(&self) -> String {
-        String::from("21.1")
+    fn version(&self) -> Result<String, Error> {
+        Ok(String::from("21.1"))
     }
 }
 
@@ -191,3 +191,5 @@ pub struct ScoreCard21 {
 }
 
 impl ScorecardCsv for ScoreCard21 {}
+
+impl ScorecardJson for ScoreCard21 {}
@@ -1,4 +1,4 @@
-use super::{Scorecard, ScorecardCsv, Size};
+use super::{Scorecard, ScorecardCsv, ScorecardJson, Size};
 use crate::{Dataset, Error, PFB_S3_PUBLIC_DOCUMENTS, PFB_S3_STORAGE_BASE_URL};
 use serde::Deserialize;
 use url::Url;
@@ -113,6 +113,8 @@ pub struct ScoreCard23 {
 
 impl ScorecardCsv for ScoreCard23 {}
 
+impl ScorecardJson for ScoreCard23 {}
+
 impl Scorecard for ScoreCard23 {
     fn full_name(&self) -> String {
         format!(
@@ -137,7 +139,7 @@ impl Scorecard for ScoreCard23 {
         Ok(Url::parse(&dataset_url)?)
     }
 
-    fn version(&self) -> String {
-        String::from("23.1")
+    fn version(&self) -> Result<String, Error> {
+        Ok(String::from("23.1"))
     }
 }
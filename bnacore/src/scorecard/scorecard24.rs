@@ -1,6 +1,7 @@
-use super::{Scorecard, ScorecardCsv, Size};
-use crate::{Dataset, Error, PFB_S3_PUBLIC_DOCUMENTS, PFB_S3_STORAGE_BASE_URL};
+use super::{Scorecard, ScorecardCsv, ScorecardJson, Size};
+use crate::{versioning::Calver, Dataset, Error, PFB_S3_PUBLIC_DOCUMENTS, PFB_S3_STORAGE_BASE_URL};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
 use time::OffsetDateTime;
 use url::Url;
 
@@ -50,52 +51,241 @@ pub struct ScoreCard24 {
 
 impl ScorecardCsv for ScoreCard24 {}
 
+impl ScorecardJson for ScoreCard24 {}
+
 impl Scorecard for ScoreCard24 {
     fn full_name(&self) -> String {
         format!("{}-{}-{}", self.country, self.state_full, self.city)
     }
 
     fn url(&self, dataset: &Dataset) -> Result<Url, Error> {
-        let mut dataset_url: String = String::new();
-        if *dataset == Dataset::DataDictionary {
-            dataset_url.push_str(PFB_S3_PUBLIC_DOCUMENTS);
-        } else {
-            dataset_url.push_str(PFB_S3_STORAGE_BASE_URL);
-            dataset_url.push('/');
-            dataset_url.push_str(&self.bna_uuid);
-        }
-        dataset_url.push('/');
-        dataset_url.push_str(&dataset.to_string());
-        dataset_url.push('.');
-        dataset_url.push_str(&dataset.extension());
-        Ok(Url::parse(&dataset_url)?)
+        self.url_with_base(dataset, PFB_S3_STORAGE_BASE_URL, PFB_S3_PUBLIC_DOCUMENTS)
     }
 
-    fn version(&self) -> String {
+    fn version(&self) -> Result<String, Error> {
         extract_version_from_filename(&self.filename)
     }
 }
 
-impl ScoreCard24 {}
+impl ScoreCard24 {
+    /// Return the URL of a dataset, using specific storage locations.
+    ///
+    /// The data dictionary is located under `public_docs_base`, and the other
+    /// datasets under `storage_base`, in a folder named after the BNA UUID.
+    ///
+    /// The path segments appended to the base are percent-encoded, therefore
+    /// values containing spaces, slashes or non-ASCII characters still
+    /// produce a valid URL.
+    pub fn url_with_base(
+        &self,
+        dataset: &Dataset,
+        storage_base: &str,
+        public_docs_base: &str,
+    ) -> Result<Url, Error> {
+        let is_data_dictionary = *dataset == Dataset::DataDictionary;
+        let base = if is_data_dictionary {
+            public_docs_base
+        } else {
+            storage_base
+        };
+        let mut dataset_url = Url::parse(base)?;
+        {
+            let mut segments = dataset_url
+                .path_segments_mut()
+                .map_err(|_| Error::InvalidArgument(format!("{base} cannot be a base URL")))?;
+            segments.pop_if_empty();
+            if !is_data_dictionary {
+                segments.push(&self.bna_uuid);
+            }
+            segments.push(&format!("{}.{}", dataset, dataset.extension()));
+        }
+        Ok(dataset_url)
+    }
+
+    /// Read a CSV file of historical results and keep only the latest result
+    /// of each city.
+    ///
+    /// The cities are identified by their BNA ID, and the result with the
+    /// greatest creation date is kept. The cities appear in the same order as
+    /// in the file.
+    pub fn latest_per_city<P>(path: P) -> Result<Vec<ScoreCard24>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut indices: HashMap<String, usize> = HashMap::new();
+        let mut scorecards: Vec<ScoreCard24> = Vec::new();
+        for scorecard in ScoreCard24::from_csv_iter(path)? {
+            let scorecard = scorecard?;
+            match indices.get(&scorecard.bna_id) {
+                Some(&i) => {
+                    if scorecard.creation_date > scorecards[i].creation_date {
+                        scorecards[i] = scorecard;
+                    }
+                }
+                None => {
+                    indices.insert(scorecard.bna_id.clone(), scorecards.len());
+                    scorecards.push(scorecard);
+                }
+            }
+        }
+        Ok(scorecards)
+    }
+
+    /// Validate the scorecard values.
+    ///
+    /// Return the list of problems found, if any:
+    /// - scores outside of the 0-100 range,
+    /// - a rounded score which does not match the overall score,
+    /// - an empty BNA UUID.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems: Vec<String> = Vec::new();
+
+        let scores = [
+            ("bna_people", self.bna_people),
+            (
+                "bna_opportunity_employment",
+                self.bna_opportunity_employment,
+            ),
+            (
+                "bna_opportunity_k12_education",
+                self.bna_opportunity_k12_education,
+            ),
+            (
+                "bna_opportunity_technical_vocational_college",
+                self.bna_opportunity_technical_vocational_college,
+            ),
+            (
+                "bna_opportunity_higher_education",
+                self.bna_opportunity_higher_education,
+            ),
+            ("bna_opportunity", self.bna_opportunity),
+            ("bna_core_services_doctors", self.bna_core_services_doctors),
+            (
+                "bna_core_services_dentists",
+                self.bna_core_services_dentists,
+            ),
+            (
+                "bna_core_services_hospitals",
+                self.bna_core_services_hospitals,
+            ),
+            (
+                "bna_core_services_pharmacies",
+                self.bna_core_services_pharmacies,
+            ),
+            ("bna_core_services_grocery", self.bna_core_services_grocery),
+            (
+                "bna_core_services_social_services",
+                self.bna_core_services_social_services,
+            ),
+            ("bna_core_services", self.bna_core_services),
+            (
+                "bna_recreation_community_centers",
+                self.bna_recreation_community_centers,
+            ),
+            ("bna_recreation_parks", self.bna_recreation_parks),
+            ("bna_recreation_trails", self.bna_recreation_trails),
+            ("bna_recreation", self.bna_recreation),
+            ("bna_retail", self.bna_retail),
+            ("bna_transit", self.bna_transit),
+            ("bna_overall_score", self.bna_overall_score),
+        ];
+        for (field, score) in scores {
+            if let Some(score) = score {
+                if !(0.0..=100.0).contains(&score) {
+                    problems.push(format!("{field} must be between 0 and 100, got {score}"));
+                }
+            }
+        }
+
+        if self.bna_rounded_score > 100 {
+            problems.push(format!(
+                "bna_rounded_score must be between 0 and 100, got {}",
+                self.bna_rounded_score
+            ));
+        }
+        if let Some(overall_score) = self.bna_overall_score {
+            if overall_score.round() != f64::from(self.bna_rounded_score) {
+                problems.push(format!(
+                    "bna_rounded_score ({}) does not match bna_overall_score ({overall_score})",
+                    self.bna_rounded_score
+                ));
+            }
+        }
+
+        if self.bna_uuid.trim().is_empty() {
+            problems.push("bna_uuid must not be empty".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
 
 /// Extract the version number from the scorecard filename.
-fn extract_version_from_filename(filename: &str) -> String {
-    let parts = filename.split('_');
-    let version_part = parts.last().unwrap();
-    let v_version = version_part.replace(".csv", "");
-    v_version.replace('v', "")
+///
+/// The version is expected to be the last part of the file name, prefixed with
+/// a `v`, e.g. `_Christchurch_v23.1.csv`.
+fn extract_version_from_filename(filename: &str) -> Result<String, Error> {
+    let version_part = filename
+        .trim_end_matches(".csv")
+        .rsplit('_')
+        .next()
+        .unwrap_or_default();
+    let version = version_part.strip_prefix('v').ok_or_else(|| {
+        Error::InvalidArgument(format!("no version found in file name: {filename}"))
+    })?;
+    Calver::try_from_ubuntu(version).map_err(|e| {
+        Error::InvalidArgument(format!("invalid version in file name {filename}: {e}"))
+    })?;
+    Ok(version.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
     use time::macros::datetime;
 
+    #[rstest]
+    #[case("_Christchurch_v23.1.csv", Some("23.1"))]
+    #[case("VIC_Yarra LGA_v24.05.2.csv", Some("24.05.2"))]
+    #[case("_Christchurch.csv", None)]
+    #[case("_Christchurch_v23.csv", None)]
+    #[case("_Christchurch_vXX.Y.csv", None)]
+    fn test_extract_version_from_filename(#[case] filename: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            extract_version_from_filename(filename).ok().as_deref(),
+            expected
+        );
+    }
+
     #[test]
-    fn test_extract_version_from_filename() {
+    fn test_latest_per_city() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("city-ratings-all-historical-results.csv");
+        let csv = [
+            "city,state,state_full,country,region,year,census_fips_code,census_population,census_latitude,census_longitude,residential_speed_limit,bna_id,bna_uuid,bna_people,bna_opportunity_employment,bna_opportunity_k12_education,bna_opportunity_technical_vocational_college,bna_opportunity_higher_education,bna_opportunity,bna_core_services_doctors,bna_core_services_dentists,bna_core_services_hospitals,bna_core_services_pharmacies,bna_core_services_grocery,bna_core_services_social_services,bna_core_services,bna_retail,bna_recreation_parks,bna_recreation_trails,bna_recreation_community_centers,bna_recreation,bna_transit,bna_overall_score,bna_rounded_score,bna_total_low_stress_miles,bna_total_high_stress_miles,pop_size,rank,rank_size,rank_state,rank_country,rank_country_size,creation_date,filename",
+            "Antwerp,VAN,Antwerp,Belgium,Europe,2023,9900041,530504,51.2194,4.4025,25,6d1927b4-3474-4ce0-9b2e-2a1f5a7d91bd,219082f0-4485-450c-a6b1-16a45dda3c47,86.88,0,90.88,81.46,82.79,86.94,87.3,77.03,88.97,90.23,93.84,88.53,88.72,75.41,90.16,98.05,81.41,90.73,79.13,84.95,85,1862.2,437.8,large,,,,,,2023-01-06T16:22:00Z,_Antwerp_v23.1.csv",
+            "Bruges,VWV,West Flanders,Belgium,Europe,2023,9900042,116855,51.2167,3.2333,25,728b3357-89b9-4c3b-9af3-5e45acadaf40,cd904d2b-90fa-445d-846d-bdc2b95c76c0,72.4,0,75.76,84.26,79.78,78.3,70.62,65.78,79.75,71.79,77.98,79.77,75.29,64.53,79.61,85.78,69.67,79.28,69.99,73.65,74,957.8,299.2,medium,,,,,,2023-01-09T14:31:00Z,_Bruges_v23.2.csv",
+            "Antwerp,VAN,Antwerp,Belgium,Europe,2023,9900041,530504,51.2194,4.4025,25,6d1927b4-3474-4ce0-9b2e-2a1f5a7d91bd,1a759b85-cd87-4bb1-9efa-5789e38e9982,75.81,0,76.63,81.67,84.76,79.91,73.51,68.69,82.43,76.62,83.02,77.82,78.15,73.71,78.49,94.45,70.7,82.13,71.59,77.1,77,1862.2,437.8,large,,,,,,2023-01-09T14:30:00Z,_Antwerp_v23.2.csv",
+        ]
+        .join("\n");
+        std::fs::write(&path, csv).unwrap();
+
+        let scorecards = ScoreCard24::latest_per_city(&path).unwrap();
+        let results = scorecards
+            .iter()
+            .map(|s| (s.city.as_str(), s.version().unwrap()))
+            .collect::<Vec<(&str, String)>>();
         assert_eq!(
-            extract_version_from_filename("_Christchurch_v23.1.csv"),
-            "23.1"
+            results,
+            vec![
+                ("Antwerp", "23.2".to_string()),
+                ("Bruges", "23.2".to_string())
+            ]
         );
     }
 
@@ -189,4 +379,161 @@ mod tests {
         let deserialized = serde_json::from_str::<ScoreCard24>(raw_json).unwrap();
         assert_eq!(deserialized.state, Some("CAN".to_string()));
     }
+
+    #[test]
+    fn test_json_round_trip() {
+        let raw_json = r#"
+          [{
+            "city": "Christchurch",
+            "state": "CAN",
+            "state_full": "Canterbury",
+            "country": "New Zealand",
+            "region": "New Zealand",
+            "year": 2023,
+            "census_fips_code": 9900246,
+            "census_population": 389300,
+            "census_latitude": -43.532,
+            "census_longitude": 172.6306,
+            "residential_speed_limit": 25,
+            "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+            "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+            "bna_people": 48.26,
+            "bna_overall_score": 52.97,
+            "bna_rounded_score": 53,
+            "pop_size": "large",
+            "creation_date": "2023-04-17T16:31:00Z",
+            "filename": "_Christchurch_v23.1.csv"
+          }]"#;
+        let scorecards = serde_json::from_str::<Vec<ScoreCard24>>(raw_json).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scorecards.json");
+        ScoreCard24::to_json(&path, &scorecards).unwrap();
+
+        let deserialized = ScoreCard24::from_json(&path).unwrap();
+        assert_eq!(deserialized.len(), 1);
+        assert_eq!(deserialized[0].city, "Christchurch");
+        assert_eq!(deserialized[0].bna_overall_score, Some(52.97));
+        assert_eq!(deserialized[0].creation_date, scorecards[0].creation_date);
+    }
+
+    #[test]
+    fn test_validate() {
+        let raw_json = r#"
+          {
+            "city": "Christchurch",
+            "state": "CAN",
+            "state_full": "Canterbury",
+            "country": "New Zealand",
+            "region": "New Zealand",
+            "year": 2023,
+            "census_population": 389300,
+            "census_latitude": -43.532,
+            "census_longitude": 172.6306,
+            "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+            "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+            "bna_people": 48.26,
+            "bna_overall_score": 52.97,
+            "bna_rounded_score": 53,
+            "creation_date": "2023-04-17T16:31:00Z",
+            "filename": "_Christchurch_v23.1.csv"
+          }"#;
+        let mut scorecard = serde_json::from_str::<ScoreCard24>(raw_json).unwrap();
+        assert!(scorecard.validate().is_ok());
+
+        scorecard.bna_people = Some(148.26);
+        let problems = scorecard.validate().unwrap_err();
+        assert_eq!(
+            problems,
+            vec!["bna_people must be between 0 and 100, got 148.26".to_string()]
+        );
+    }
+
+    fn christchurch() -> ScoreCard24 {
+        let raw_json = r#"
+          {
+            "city": "Christchurch",
+            "state": "CAN",
+            "state_full": "Canterbury",
+            "country": "New Zealand",
+            "region": "New Zealand",
+            "year": 2023,
+            "census_population": 389300,
+            "census_latitude": -43.532,
+            "census_longitude": 172.6306,
+            "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+            "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+            "bna_rounded_score": 53,
+            "creation_date": "2023-04-17T16:31:00Z",
+            "filename": "_Christchurch_v23.1.csv"
+          }"#;
+        serde_json::from_str::<ScoreCard24>(raw_json).unwrap()
+    }
+
+    #[test]
+    fn test_dataset_urls() {
+        let scorecard = christchurch();
+        let urls = scorecard
+            .dataset_urls(&[Dataset::OverallScores, Dataset::DataDictionary])
+            .unwrap();
+        assert_eq!(
+            urls,
+            vec![
+                (
+                    Dataset::OverallScores,
+                    Url::parse("https://s3.amazonaws.com/production-pfb-storage-us-east-1/results/9ac2465b-04f7-48a0-adc6-92502243b6e3/neighborhood_overall_scores.csv").unwrap()
+                ),
+                (
+                    Dataset::DataDictionary,
+                    Url::parse("https://s3.amazonaws.com/pfb-public-documents/BNA.Data.Dictionary.xlsx").unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_url_with_base() {
+        let scorecard = christchurch();
+        let storage_base = "https://s3.amazonaws.com/staging-pfb-storage-us-east-1/results";
+        let public_docs_base = "https://s3.amazonaws.com/staging-pfb-public-documents";
+        assert_eq!(
+            scorecard
+                .url_with_base(&Dataset::OverallScores, storage_base, public_docs_base)
+                .unwrap(),
+            Url::parse("https://s3.amazonaws.com/staging-pfb-storage-us-east-1/results/9ac2465b-04f7-48a0-adc6-92502243b6e3/neighborhood_overall_scores.csv").unwrap()
+        );
+        assert_eq!(
+            scorecard
+                .url_with_base(&Dataset::DataDictionary, storage_base, public_docs_base)
+                .unwrap(),
+            Url::parse(
+                "https://s3.amazonaws.com/staging-pfb-public-documents/BNA.Data.Dictionary.xlsx"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_url_with_base_encoding() {
+        let mut scorecard = christchurch();
+        scorecard.bna_uuid = "Ōtautahi results/2023".to_string();
+        let url = scorecard
+            .url_with_base(
+                &Dataset::OverallScores,
+                "https://s3.amazonaws.com/staging bucket/results/",
+                PFB_S3_PUBLIC_DOCUMENTS,
+            )
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://s3.amazonaws.com/staging%20bucket/results/%C5%8Ctautahi%20results%2F2023/neighborhood_overall_scores.csv"
+        );
+    }
+
+    #[test]
+    fn test_url_with_base_invalid() {
+        let scorecard = christchurch();
+        assert!(scorecard
+            .url_with_base(&Dataset::OverallScores, "mailto:bna@example.com", "")
+            .is_err());
+    }
 }
@@ -1,7 +1,11 @@
 use super::{
-    scorecard21::ScoreCard21, scorecard23::ScoreCard23, scorecard24::ScoreCard24, ScorecardCsv,
+    scorecard21::ScoreCard21, scorecard23::ScoreCard23, scorecard24::ScoreCard24, Format,
+    ScorecardCsv, ScorecardJson,
 };
+use crate::Error;
+use csv::WriterBuilder;
 use serde::Serialize;
+use std::path::Path;
 
 /// Represent a ScoreCard to be passed to `svggloo`.
 ///
@@ -143,3 +147,155 @@ impl From<&ScoreCard24> for ShortScoreCard {
 }
 
 impl ScorecardCsv for ShortScoreCard {}
+
+impl ScorecardJson for ShortScoreCard {}
+
+impl ShortScoreCard {
+    /// Represent the CSV columns, in the order they are written.
+    ///
+    /// The templates consuming the CSV file rely on these names, therefore
+    /// they must not change. They must also match the order of the fields of
+    /// the struct, which are serialized in that order.
+    pub const HEADERS: [&'static str; 23] = [
+        "ci",
+        "co",
+        "st",
+        "uuid",
+        "po",
+        "ra",
+        "rasc",
+        "nw",
+        "aw",
+        "sf",
+        "rs",
+        "total",
+        "cssc",
+        "responses",
+        "nh",
+        "op",
+        "es",
+        "ret",
+        "rec",
+        "tr",
+        "bnasc",
+        "lsm",
+        "hsm",
+    ];
+
+    /// Read a City Ratings CSV file in a specific format and convert its
+    /// records to ShortScoreCards.
+    pub fn from_city_ratings<P>(format: &Format, path: P) -> Result<Vec<ShortScoreCard>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        match format {
+            Format::V21 => ScoreCard21::from_csv_iter(path)?
+                .map(|e| e.map(|e| ShortScoreCard::from(&e)))
+                .collect(),
+            Format::V23 => ScoreCard23::from_csv_iter(path)?
+                .map(|e| e.map(|e| ShortScoreCard::from(&e)))
+                .collect(),
+            Format::V24 => ScoreCard24::from_csv_iter(path)?
+                .map(|e| e.map(|e| ShortScoreCard::from(&e)))
+                .collect(),
+        }
+    }
+
+    /// Saves a slice of ShortScoreCards to a CSV file.
+    ///
+    /// Unlike [`ScorecardCsv::to_csv`], the header row is always written, even
+    /// if there are no entries.
+    pub fn write_csv<P>(path: P, entries: &[ShortScoreCard]) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut w = WriterBuilder::new().has_headers(false).from_path(path)?;
+        w.write_record(Self::HEADERS)?;
+        for entry in entries {
+            w.serialize(entry)?;
+        }
+        Ok(w.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provincetown() -> ShortScoreCard {
+        ShortScoreCard {
+            ci: "Provincetown".to_string(),
+            co: "United States".to_string(),
+            st: "MA".to_string(),
+            uuid: "34330464-1e70-4988-9f92-52e3c6a869c0".to_string(),
+            po: 3664,
+            ra: 56.3,
+            rasc: 56,
+            nw: 0,
+            aw: 0,
+            sf: 0,
+            rs: 0,
+            total: 0,
+            cssc: 0,
+            responses: 0,
+            nh: 81,
+            op: 87,
+            es: 88,
+            ret: 74,
+            rec: 86,
+            tr: 85,
+            bnasc: 56,
+            lsm: 48,
+            hsm: 12,
+        }
+    }
+
+    #[test]
+    fn test_headers_match_fields() {
+        let mut w = csv::Writer::from_writer(vec![]);
+        w.serialize(provincetown()).unwrap();
+        let content = String::from_utf8(w.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            content.lines().next(),
+            Some(ShortScoreCard::HEADERS.join(",").as_str())
+        );
+    }
+
+    #[test]
+    fn test_write_csv_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brochure.csv");
+        ShortScoreCard::write_csv(&path, &[provincetown()]).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(
+            lines.next(),
+            Some("ci,co,st,uuid,po,ra,rasc,nw,aw,sf,rs,total,cssc,responses,nh,op,es,ret,rec,tr,bnasc,lsm,hsm")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("Provincetown,United States,MA,34330464-1e70-4988-9f92-52e3c6a869c0,3664,56.3,56,0,0,0,0,0,0,0,81,87,88,74,86,85,56,48,12")
+        );
+
+        // The header is written even without any entry.
+        ShortScoreCard::write_csv(&path, &[]).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.trim_end(), ShortScoreCard::HEADERS.join(","));
+    }
+
+    #[test]
+    fn test_write_csv_float_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brochure.csv");
+        let scorecard = ShortScoreCard {
+            ra: 56.0,
+            ..provincetown()
+        };
+        ShortScoreCard::write_csv(&path, &[scorecard]).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let record = content.lines().nth(1).unwrap();
+        assert_eq!(record.split(',').nth(5), Some("56.0"));
+    }
+}
@@ -1,71 +1,206 @@
 use crate::Error;
 use csv::Reader;
 use minijinja::Environment;
+use rayon::{prelude::*, ThreadPoolBuilder};
 use serde::Serialize;
+use serde_json::Value;
 use std::{
     collections::HashMap,
-    fs,
-    path::{Path, PathBuf},
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::BufReader,
+    path::{Component, Path, PathBuf},
     process::Command,
+    sync::Arc,
+};
+use svg2pdf::{
+    usvg::{fontdb::Database, Options, Tree},
+    ConversionOptions, PageOptions,
 };
 
 type Record = HashMap<String, String>;
 
+/// Name under which the output filename template is registered.
+const FILENAME_TEMPLATE_NAME: &str = "__filename__";
+
+/// Describe the outcome of a rendering operation.
+#[derive(Debug, Default)]
+pub struct RenderReport {
+    /// Files which were rendered successfully, in the order of the records.
+    pub rendered: Vec<PathBuf>,
+    /// Records which could not be rendered, identified by their index in the
+    /// data file (starting at 0, not counting the CSV header).
+    pub failures: Vec<(usize, Error)>,
+}
+
+impl RenderReport {
+    /// Return `true` if all the records were rendered successfully.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for RenderReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} record(s) rendered, {} record(s) failed",
+            self.rendered.len(),
+            self.failures.len()
+        )
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Exporter {
     Inkscape,
     CairoSVG,
     SVG2PDF,
+    /// Only render the SVG files, without exporting them.
+    None,
+}
+
+/// Define the formats of the data file.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DataFormat {
+    /// CSV file with a header row.
+    #[default]
+    Csv,
+    /// JSON array of objects.
+    Json,
+}
+
+impl DataFormat {
+    /// Return the extension of the data file.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DataFormat::Csv => "csv",
+            DataFormat::Json => "json",
+        }
+    }
+}
+
+/// Customize the MiniJinja environment used to render a template.
+pub type EnvHook = Arc<dyn Fn(&mut Environment) + Send + Sync>;
+
+/// Define the options of a rendering operation.
+///
+/// The default options render the records of a CSV file sequentially, without
+/// exporting the rendered files.
+#[derive(Clone, Default)]
+pub struct RenderOptions {
+    /// Export the rendered files with this exporter.
+    pub exporter: Option<Exporter>,
+    /// Fields of a record used to name its output file.
+    ///
+    /// The values of the fields are transformed to lowercase and concatenated
+    /// together using the `separator`, in the order they were specified.
+    pub field_based_name: Option<Vec<String>>,
+    /// Separator of the field values in the output file names, dash (`-`) by
+    /// default.
+    pub separator: Option<String>,
+    /// MiniJinja template rendered against each record to build the path of
+    /// its output file, relative to the output directory, including its
+    /// extension, for instance `{{co}}/{{st}}/{{ci}}-scorecard.svg`.
+    ///
+    /// The intermediate directories are created as needed. When set,
+    /// `field_based_name` and `separator` are ignored.
+    pub filename_template: Option<String>,
+    /// Format of the data file.
+    pub data_format: DataFormat,
+    /// Render the records using at most this number of threads.
+    pub jobs: Option<usize>,
+    /// Hook called with the MiniJinja [`Environment`] before the template gets
+    /// compiled, for instance to register custom filters.
+    pub configure: Option<EnvHook>,
+    /// Fonts used by the SVG2PDF exporter, the system fonts by default.
+    pub fontdb: Option<Arc<Database>>,
+}
+
+impl RenderOptions {
+    /// Customize the MiniJinja environment used to render the template.
+    pub fn with_env<F>(mut self, configure: F) -> Self
+    where
+        F: Fn(&mut Environment) + Send + Sync + 'static,
+    {
+        self.configure = Some(Arc::new(configure));
+        self
+    }
+}
+
+impl fmt::Debug for RenderOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderOptions")
+            .field("exporter", &self.exporter)
+            .field("field_based_name", &self.field_based_name)
+            .field("separator", &self.separator)
+            .field("filename_template", &self.filename_template)
+            .field("data_format", &self.data_format)
+            .field("jobs", &self.jobs)
+            .field("configure", &self.configure.as_ref().map(|_| "EnvHook"))
+            .field("fontdb", &self.fontdb.as_ref().map(|db| db.len()))
+            .finish()
+    }
 }
 
 /// Render an SVG template.
 ///
-/// Merges the data from the CSV file into the SVG template to create a new SVG
-/// file and render it to PDF.
+/// Merges the records of the data file into the SVG template to create a new
+/// SVG file for each of them, and exports them with the selected exporter.
 ///
-/// The `field_based_name` argument can be used to specify one or several fields
-/// from the CSV file that must be used to name the output files. If the fields
-/// don't exist, this function will panic. Once all the fields are being
-/// collected, they are transformed to lowercase and concatenated together using
-/// the `separator`, in the order they were specified.
+/// The data file has the same name as the template, with the extension of the
+/// [`DataFormat`]. A JSON file must contain an array of objects, for instance
+/// the `CityRating` objects emitted by the API. Their values are converted to
+/// strings, therefore the output files are named exactly like with a CSV file
+/// containing the same data.
 ///
-/// If `field_based_name` is not specified, it defaults to the first field of a
-/// CSV record.
+/// If neither `field_based_name` nor `filename_template` are specified, the
+/// output files are named after the first field of a record.
 ///
-/// If `separator` is not specified, it defaults to dash (`-`).
+/// When `jobs` is set, the records are rendered concurrently, and the rendered
+/// files are split into at most `jobs` batches, each exported by its own
+/// process. This prevents spawning one Inkscape or CairoSVG process per record.
+///
+/// A record which cannot be rendered, for instance because it is malformed or
+/// because one of the `field_based_name` fields is missing, does not abort the
+/// operation. It is reported in the [`RenderReport`] along with its error, and
+/// the other records are rendered and exported normally. Only the rendered files
+/// are exported.
 ///
 /// ```no_run
 /// # use color_eyre::{eyre::Report, Result};
 /// use std::path::Path;
-/// use bnacore::template::{render, Exporter};
+/// use bnacore::template::{render, Exporter, RenderOptions};
 ///
 /// # fn main() -> Result<(), Report> {
 /// let svg_template = Path::new("SVG_TEMPLATE_FILENAME");
 /// let output_dir = Path::new("OUTPUT_DIR");
-/// let fields = vec![
-///     String::from("country"),
-///     String::from("state"),
-///     String::from("city"),
-/// ];
-/// let _ = render(
-///     &svg_template.canonicalize()?,
-///     output_dir,
-///     Some(Exporter::CairoSVG),
-///     Some(fields),
-///     None,
-/// )?;
+/// let options = RenderOptions {
+///     exporter: Some(Exporter::CairoSVG),
+///     field_based_name: Some(vec![
+///         String::from("country"),
+///         String::from("state"),
+///         String::from("city"),
+///     ]),
+///     jobs: Some(4),
+///     ..RenderOptions::default()
+/// }
+/// .with_env(|env| env.add_filter("shout", |value: String| value.to_uppercase()));
+/// let _ = render(&svg_template.canonicalize()?, output_dir, &options)?;
 /// # Ok(())
 /// # }
 /// ```
 pub fn render(
     svg_template: &Path,
     output_dir: &Path,
-    exporter: Option<Exporter>,
-    field_based_name: Option<Vec<String>>,
-    separator: Option<&str>,
-) -> Result<(), Error> {
+    options: &RenderOptions,
+) -> Result<RenderReport, Error> {
+    let exporter = options.exporter;
+    let data_format = options.data_format;
+    let jobs = options.jobs.map(|jobs| jobs.max(1));
+
     // Locate the template file data and the prepare the output directory.
-    let template_data = svg_template.with_extension("csv");
+    let template_data = svg_template.with_extension(data_format.extension());
     fs::create_dir_all(output_dir)?;
 
     // Load the template.
@@ -76,48 +211,185 @@ pub fn render(
         .to_str()
         .unwrap();
     let mut env = Environment::new();
+    if let Some(configure) = &options.configure {
+        configure(&mut env);
+    }
     env.add_template(name, &source)?;
+
+    // Load the filename template.
+    if let Some(filename_template) = &options.filename_template {
+        env.add_template(FILENAME_TEMPLATE_NAME, filename_template)?;
+    }
     let tmpl = env.get_template(name).unwrap();
+    let filename_tmpl = options
+        .filename_template
+        .as_ref()
+        .map(|_| env.get_template(FILENAME_TEMPLATE_NAME))
+        .transpose()?;
 
     // Set the separator.
-    let sep = separator.unwrap_or("-");
-
-    // Read the CSV.
-    let mut csv_reader = Reader::from_path(template_data)?;
-    let mut files: Vec<PathBuf> = Vec::new();
-    for result in csv_reader.deserialize() {
-        let record: Record = result?;
-        let mut item_name = String::new();
-        if let Some(fields) = &field_based_name {
+    let sep = options.separator.as_deref().unwrap_or("-");
+
+    // Read the records.
+    let records = read_records(&template_data, data_format)?;
+
+    // Prepare the font database used by the SVG2PDF exporter.
+    let fontdb = match exporter {
+        Some(Exporter::SVG2PDF) => options.fontdb.clone().unwrap_or_else(system_fontdb),
+        _ => Arc::new(Database::new()),
+    };
+
+    // Render the template to file for a specific record.
+    let render_one = |record: Result<Record, Error>| -> Result<PathBuf, Error> {
+        let record = record?;
+        let item = match &filename_tmpl {
+            Some(filename_tmpl) => relative_path(&filename_tmpl.render(&record)?)?,
+            None => {
+                let mut item = item_name(&record, options.field_based_name.as_deref(), sep)?;
+                item.push_str(".svg");
+                PathBuf::from(item)
+            }
+        };
+        let rendered = tmpl.render(&record)?;
+        let output_file = output_dir.join(&item);
+        if let Some(parent) = output_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&output_file, rendered)?;
+        Ok(output_file)
+    };
+
+    // Prepare the thread pool used in parallel mode.
+    let pool = jobs
+        .map(|jobs| {
+            ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .map(|pool| (pool, jobs))
+        })
+        .transpose()?;
+
+    // Render the records.
+    let results = match &pool {
+        None => records.into_iter().map(render_one).collect::<Vec<_>>(),
+        Some((pool, _)) => {
+            pool.install(|| records.into_par_iter().map(render_one).collect::<Vec<_>>())
+        }
+    };
+    let mut report = RenderReport::default();
+    for (index, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(file) => report.rendered.push(file),
+            Err(e) => report.failures.push((index, e)),
+        }
+    }
+
+    // Convert the rendered files to pdf.
+    if let Some(exporter) = exporter {
+        match &pool {
+            Some((pool, jobs)) => {
+                let batch_size = report.rendered.len().div_ceil(*jobs).max(1);
+                pool.install(|| {
+                    report
+                        .rendered
+                        .par_chunks(batch_size)
+                        .try_for_each(|batch| export(exporter, batch, &fontdb))
+                })?;
+            }
+            None => export(exporter, &report.rendered, &fontdb)?,
+        }
+    }
+
+    Ok(report)
+}
+
+/// Read the records of a data file.
+///
+/// A record which cannot be read is returned as an error, without aborting the
+/// operation.
+fn read_records(path: &Path, data_format: DataFormat) -> Result<Vec<Result<Record, Error>>, Error> {
+    match data_format {
+        DataFormat::Csv => Ok(Reader::from_path(path)?
+            .deserialize()
+            .map(|record| record.map_err(Error::from))
+            .collect()),
+        DataFormat::Json => {
+            let values: Vec<Value> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+            Ok(values.into_iter().map(json_record).collect())
+        }
+    }
+}
+
+/// Convert a JSON object to a record.
+///
+/// The strings are used as is, and the other values are serialized to JSON.
+fn json_record(value: Value) -> Result<Record, Error> {
+    match value {
+        Value::Object(map) => Ok(map
+            .into_iter()
+            .map(|(k, v)| match v {
+                Value::String(s) => (k, s),
+                Value::Null => (k, String::new()),
+                v => (k, v.to_string()),
+            })
+            .collect()),
+        v => Err(Error::InvalidArgument(format!(
+            "a record must be a JSON object, not `{v}`"
+        ))),
+    }
+}
+
+/// Build the name of the output file for a record, without its extension.
+fn item_name(
+    record: &Record,
+    field_based_name: Option<&[String]>,
+    separator: &str,
+) -> Result<String, Error> {
+    match field_based_name {
+        Some(fields) => {
             let field_values = fields
                 .iter()
-                .map(|f| record[f].clone())
-                .collect::<Vec<String>>();
-            let name = field_values.join(sep);
-            item_name = name
+                .map(|f| {
+                    record.get(f).cloned().ok_or_else(|| {
+                        Error::InvalidArgument(format!("the field `{f}` does not exist"))
+                    })
+                })
+                .collect::<Result<Vec<String>, Error>>()?;
+            Ok(field_values
+                .join(separator)
                 .to_lowercase()
                 .replace(' ', "_")
                 .chars()
                 .filter(|c| c.is_alphabetic() || *c == '-' || *c == '_' || *c == '.')
-                .collect::<String>();
+                .collect::<String>())
         }
-        let mut item = item_name.clone();
-        item.push_str(".svg");
+        None => Ok(String::new()),
+    }
+}
 
-        // Render the template to file for this specific record.
-        let rendered = tmpl.render(&record)?;
-        let output_file = output_dir.join(&item);
-        fs::write(&output_file, rendered)?;
-        files.push(output_file);
+/// Validate a rendered output file name.
+///
+/// The name must be a relative path which stays within the output directory.
+fn relative_path(name: &str) -> Result<PathBuf, Error> {
+    let path = PathBuf::from(name.trim());
+    let is_contained = path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if path.file_name().is_none() || !is_contained {
+        return Err(Error::InvalidArgument(format!(
+            "invalid output file name `{name}`"
+        )));
     }
+    Ok(path)
+}
 
-    // Convert it to pdf.
-    if let Some(exporter) = exporter {
-        match exporter {
-            Exporter::Inkscape => export_with_inkscape(&files),
-            Exporter::CairoSVG => export_with_cairosvg(&files),
-            Exporter::SVG2PDF => export_with_svg2pdf(&files),
-        }
+/// Convert the rendered SVG files to PDF with the selected exporter.
+fn export(exporter: Exporter, files: &[PathBuf], fontdb: &Arc<Database>) -> Result<(), Error> {
+    match exporter {
+        Exporter::Inkscape => export_with_inkscape(files),
+        Exporter::CairoSVG => export_with_cairosvg(files),
+        Exporter::SVG2PDF => export_with_svg2pdf(files, fontdb.clone())?,
+        Exporter::None => {}
     }
     Ok(())
 }
@@ -222,17 +494,50 @@ pub fn export_with_cairosvg(srcs: &[PathBuf]) {
     }
 }
 
-pub fn export_with_svg2pdf(srcs: &[PathBuf]) {
+/// Exports an SVG file to a PDF with svg2pdf.
+///
+/// Exports an SVG `src` file as a PDF with the same name.
+///
+/// The conversion is done in-process, therefore no external program is
+/// required. The text is rendered using the fonts from the `fontdb` database.
+///
+/// ```no_run
+/// # use color_eyre::{eyre::Report, Result};
+/// use bnacore::template::{export_with_svg2pdf, system_fontdb};
+/// use std::path::PathBuf;
+///
+/// # fn main() -> Result<(), Report> {
+/// let mut fontdb = (*system_fontdb()).clone();
+/// fontdb.load_fonts_dir("assets/fonts/Montserrat");
+/// export_with_svg2pdf(&[PathBuf::from("brochure.svg")], fontdb.into())?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn export_with_svg2pdf(srcs: &[PathBuf], fontdb: Arc<Database>) -> Result<(), Error> {
+    let options = Options {
+        fontdb,
+        ..Options::default()
+    };
     for src in srcs {
-        // Prepare the input/output values from the src argument.
-        let (in_svg, out_pdf) = get_in_out_file(src);
+        let svg = fs::read_to_string(src)?;
+        let pdf = svg_to_pdf(&svg, &options)?;
+        fs::write(src.with_extension("pdf"), pdf)?;
+    }
+    Ok(())
+}
 
-        // Prepare the command.
-        let program = "svg2pdf";
-        let args = vec![in_svg, out_pdf];
+/// Create a font database containing the fonts installed on the system.
+pub fn system_fontdb() -> Arc<Database> {
+    let mut fontdb = Database::new();
+    fontdb.load_system_fonts();
+    Arc::new(fontdb)
+}
 
-        export_with(program, &args);
-    }
+/// Convert an SVG document to PDF.
+fn svg_to_pdf(svg: &str, options: &Options) -> Result<Vec<u8>, Error> {
+    let tree = Tree::from_str(svg, options).map_err(|e| Error::Svg2Pdf(e.to_string()))?;
+    svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default())
+        .map_err(|e| Error::Svg2Pdf(e.to_string()))
 }
 
 /// Get the input and output string representations of the provided file.
@@ -255,6 +560,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
 
     #[test]
     fn test_get_in_out_file() {
@@ -263,4 +569,356 @@ mod tests {
         assert_eq!(in_svg, String::from("brochure.svg"));
         assert_eq!(out_pdf, String::from("brochure.pdf"));
     }
+
+    #[test]
+    fn test_render_svg2pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("card.svg");
+        fs::write(
+            &svg_template,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="{{width}}" height="50" fill="{{color}}"/></svg>"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("card.csv"),
+            "name,width,color\nblue,80,blue\n",
+        )
+        .unwrap();
+
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            exporter: Some(Exporter::SVG2PDF),
+            field_based_name: Some(vec![String::from("name")]),
+            ..RenderOptions::default()
+        };
+        render(&svg_template, &output_dir, &options).unwrap();
+
+        let pdf = fs::read(output_dir.join("blue.pdf")).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_render_svg2pdf_fontdb() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("card.svg");
+        fs::write(
+            &svg_template,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><text x="0" y="20" font-family="Montserrat">{{name}}</text></svg>"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("card.csv"),
+            "name
+blue
+",
+        )
+        .unwrap();
+
+        let mut fontdb = Database::new();
+        fontdb.load_fonts_dir(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/fonts/Montserrat"),
+        );
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            exporter: Some(Exporter::SVG2PDF),
+            field_based_name: Some(vec![String::from("name")]),
+            fontdb: Some(Arc::new(fontdb)),
+            ..RenderOptions::default()
+        };
+        render(&svg_template, &output_dir, &options).unwrap();
+
+        let pdf = fs::read(output_dir.join("blue.pdf")).unwrap();
+        assert!(pdf.windows(10).any(|w| w == b"Montserrat"));
+    }
+
+    #[test]
+    fn test_render_exporter_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("card.svg");
+        fs::write(&svg_template, "<svg><text>{{name}}</text></svg>").unwrap();
+        fs::write(dir.path().join("card.csv"), "name\nblue\nred\n").unwrap();
+
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            exporter: Some(Exporter::None),
+            field_based_name: Some(vec![String::from("name")]),
+            ..RenderOptions::default()
+        };
+        let report = render(&svg_template, &output_dir, &options).unwrap();
+
+        assert!(report.is_success());
+        let mut files = fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec!["blue.svg", "red.svg"]);
+    }
+
+    #[test]
+    fn test_render_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("brochure.svg");
+        fs::write(
+            &svg_template,
+            "<svg><text>{{city}} {{score}} {{rank}}</text></svg>",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("brochure.csv"),
+            "state,city,score,rank\nTX,Austin,45.5,\nPA,Pittsburgh,50,2\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("brochure.json"),
+            r#"[
+              {"state": "TX", "city": "Austin", "score": 45.5, "rank": null},
+              {"state": "PA", "city": "Pittsburgh", "score": 50, "rank": 2}
+            ]"#,
+        )
+        .unwrap();
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("state"), String::from("city")]),
+            ..RenderOptions::default()
+        };
+
+        let csv_dir = dir.path().join("csv");
+        let csv_report = render(&svg_template, &csv_dir, &options).unwrap();
+        let json_dir = dir.path().join("json");
+        let json_options = RenderOptions {
+            data_format: DataFormat::Json,
+            ..options
+        };
+        let json_report = render(&svg_template, &json_dir, &json_options).unwrap();
+
+        assert!(json_report.is_success());
+        assert_eq!(json_report.rendered.len(), csv_report.rendered.len());
+        for (csv_file, json_file) in csv_report.rendered.iter().zip(&json_report.rendered) {
+            assert_eq!(csv_file.file_name(), json_file.file_name());
+            assert_eq!(
+                fs::read_to_string(csv_file).unwrap(),
+                fs::read_to_string(json_file).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_json_invalid_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("card.svg");
+        fs::write(&svg_template, "<svg><text>{{name}}</text></svg>").unwrap();
+        fs::write(
+            dir.path().join("card.json"),
+            r#"[{"name": "blue"}, 42, {"name": "red"}]"#,
+        )
+        .unwrap();
+
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("name")]),
+            data_format: DataFormat::Json,
+            ..RenderOptions::default()
+        };
+        let report = render(&svg_template, &output_dir, &options).unwrap();
+
+        assert_eq!(
+            report.rendered,
+            vec![output_dir.join("blue.svg"), output_dir.join("red.svg")]
+        );
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, 1);
+    }
+
+    #[test]
+    fn test_render_report_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("brochure.svg");
+        fs::write(&svg_template, "<svg><text>{{city}}</text></svg>").unwrap();
+        // The second record is malformed and cannot be rendered.
+        fs::write(
+            dir.path().join("brochure.csv"),
+            "state,city\nTX,Austin\nCO\nPA,Pittsburgh\n",
+        )
+        .unwrap();
+
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("state"), String::from("city")]),
+            ..RenderOptions::default()
+        };
+        let report = render(&svg_template, &output_dir, &options).unwrap();
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.rendered,
+            vec![
+                output_dir.join("tx-austin.svg"),
+                output_dir.join("pa-pittsburgh.svg")
+            ]
+        );
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, 1);
+        assert!(output_dir.join("pa-pittsburgh.svg").exists());
+        assert_eq!(
+            report.to_string(),
+            "2 record(s) rendered, 1 record(s) failed"
+        );
+    }
+
+    /// Format a number with thousands separators.
+    fn thousands(value: String) -> Result<String, minijinja::Error> {
+        let number = value.parse::<u64>().map_err(|e| {
+            minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, e.to_string())
+        })?;
+        let digits = number.to_string();
+        let mut formatted = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                formatted.push(',');
+            }
+            formatted.push(c);
+        }
+        Ok(formatted)
+    }
+
+    #[test]
+    fn test_render_with_env_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("brochure.svg");
+        fs::write(&svg_template, "<svg><text>{{lsm|thousands}}</text></svg>").unwrap();
+        fs::write(
+            dir.path().join("brochure.csv"),
+            "city,lsm\nAustin,1234567\n",
+        )
+        .unwrap();
+
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("city")]),
+            ..RenderOptions::default()
+        }
+        .with_env(|env| env.add_filter("thousands", thousands));
+        let report = render(&svg_template, &output_dir, &options).unwrap();
+
+        assert!(report.is_success());
+        let rendered = fs::read_to_string(output_dir.join("austin.svg")).unwrap();
+        assert_eq!(rendered, "<svg><text>1,234,567</text></svg>");
+    }
+
+    #[test]
+    fn test_render_filename_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("scorecard.svg");
+        fs::write(&svg_template, "<svg><text>{{ci}}</text></svg>").unwrap();
+        fs::write(
+            dir.path().join("scorecard.csv"),
+            "co,st,ci\nunited_states,tx,austin\nunited_states,co,boulder\nfrance,idf,paris\n",
+        )
+        .unwrap();
+
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            filename_template: Some(String::from("{{co}}/{{st}}/{{ci}}-scorecard.svg")),
+            ..RenderOptions::default()
+        };
+        let report = render(&svg_template, &output_dir, &options).unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(
+            report.rendered,
+            vec![
+                output_dir.join("united_states/tx/austin-scorecard.svg"),
+                output_dir.join("united_states/co/boulder-scorecard.svg"),
+                output_dir.join("france/idf/paris-scorecard.svg"),
+            ]
+        );
+        let rendered =
+            fs::read_to_string(output_dir.join("france/idf/paris-scorecard.svg")).unwrap();
+        assert_eq!(rendered, "<svg><text>paris</text></svg>");
+    }
+
+    #[rstest]
+    #[case("austin.svg", true)]
+    #[case("united_states/tx/austin.svg", true)]
+    #[case("./austin.svg", true)]
+    #[case("", false)]
+    #[case("../austin.svg", false)]
+    #[case("tx/../../austin.svg", false)]
+    #[case("/tmp/austin.svg", false)]
+    fn test_relative_path(#[case] name: &str, #[case] valid: bool) {
+        assert_eq!(relative_path(name).is_ok(), valid);
+    }
+
+    /// Read all the files of a directory, sorted by name.
+    fn read_dir_sorted(dir: &Path) -> Vec<(String, String)> {
+        let mut entries = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| {
+                let path = e.unwrap().path();
+                let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+                (name, fs::read_to_string(&path).unwrap())
+            })
+            .collect::<Vec<(String, String)>>();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn test_render_parallel_matches_serial() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("brochure.svg");
+        fs::write(&svg_template, "<svg><text>{{city}}, {{state}}</text></svg>").unwrap();
+        fs::write(
+            dir.path().join("brochure.csv"),
+            "state,city\nTX,Austin\nCO,Boulder\nPA,Pittsburgh\nUT,Park City\nNC,Durham\n",
+        )
+        .unwrap();
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("state"), String::from("city")]),
+            ..RenderOptions::default()
+        };
+
+        let serial_dir = dir.path().join("serial");
+        render(&svg_template, &serial_dir, &options).unwrap();
+        let parallel_dir = dir.path().join("parallel");
+        let parallel_options = RenderOptions {
+            jobs: Some(3),
+            ..options.clone()
+        };
+        render(&svg_template, &parallel_dir, &parallel_options).unwrap();
+
+        let serial = read_dir_sorted(&serial_dir);
+        assert_eq!(serial.len(), 5);
+        assert_eq!(serial[3].0, "tx-austin.svg");
+        assert_eq!(serial[3].1, "<svg><text>Austin, TX</text></svg>");
+        assert_eq!(serial, read_dir_sorted(&parallel_dir));
+    }
+
+    #[test]
+    fn test_render_json_parallel() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg_template = dir.path().join("card.svg");
+        fs::write(&svg_template, "<svg><text>{{name}}</text></svg>").unwrap();
+        fs::write(
+            dir.path().join("card.json"),
+            r#"[{"name": "blue"}, {"name": "green"}, {"name": "red"}]"#,
+        )
+        .unwrap();
+
+        let output_dir = dir.path().join("output");
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("name")]),
+            data_format: DataFormat::Json,
+            jobs: Some(2),
+            ..RenderOptions::default()
+        };
+        let report = render(&svg_template, &output_dir, &options).unwrap();
+
+        assert!(report.is_success());
+        let files = read_dir_sorted(&output_dir)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(files, vec!["blue.svg", "green.svg", "red.svg"]);
+    }
 }
@@ -1,7 +1,12 @@
+use std::cmp::Ordering;
+
 /// Represents the Calver version scheme (calver.org).
 ///
 /// Currently only the "Ubuntu" version of the scheme is supported (YY.0M[.Micro]).
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+///
+/// Versions are ordered by their numeric value, therefore "9.12" comes before
+/// "10.01", and "24.01.2" before "24.01.10".
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Calver {
     /// Short year - 6, 16, 106
     short_year: String,
@@ -43,6 +48,29 @@ impl Calver {
         version
     }
 
+    /// Return the full year of the version.
+    ///
+    /// The short year is relative to the year 2000, therefore "24" becomes 2024.
+    pub fn year(&self) -> u32 {
+        2000 + self
+            .short_year
+            .parse::<u32>()
+            .expect("the short year must have been validated during parsing")
+    }
+
+    /// Return the numeric parts of the version, used to order versions.
+    fn key(&self) -> (u32, u32, u32) {
+        let parse = |part: &str| {
+            part.parse::<u32>()
+                .expect("the version parts must have been validated during parsing")
+        };
+        (
+            parse(&self.short_year),
+            parse(&self.zero_padded_month),
+            self.micro.as_deref().map(parse).unwrap_or_default(),
+        )
+    }
+
     fn short_year_from_str(year: &str) -> Result<String, String> {
         let y = year.parse::<u8>().map_err(|e| e.to_string())?;
         match y {
@@ -74,7 +102,7 @@ impl Calver {
                     if value == 0 {
                         Ok(None)
                     } else {
-                        Ok(Some(m.to_string()))
+                        Ok(Some(value.to_string()))
                     }
                 }
                 Err(e) => Err(e.to_string()),
@@ -83,6 +111,18 @@ impl Calver {
     }
 }
 
+impl PartialOrd for Calver {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Calver {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,6 +147,10 @@ mod tests {
     #[case("24.1", "23.1", true)]
     #[case("24.2", "23.1", true)]
     #[case("23.1", "24.1", false)]
+    #[case("10.1", "9.12", true)]
+    #[case("24.1.10", "24.1.2", true)]
+    #[case("24.1.1", "24.1", true)]
+    #[case("24.1", "24.1.0", false)]
     fn test_compare_ubuntu_calver(
         #[case] version: &str,
         #[case] other: &str,
@@ -121,9 +165,21 @@ mod tests {
     #[case("24.1", "24.1")]
     #[case("24.1", "24.1.0")]
     #[case("4.1", "04.01.0")]
+    #[case("24.1.1", "24.1.01")]
     fn test_equality_ubuntu_calver(#[case] version: &str, #[case] other: &str) {
         let c1 = Calver::try_from_ubuntu(version).unwrap();
         let c2 = Calver::try_from_ubuntu(other).unwrap();
         assert_eq!(c1, c2);
+        assert_eq!(c1.cmp(&c2), Ordering::Equal);
+        assert_eq!(c1.to_ubuntu(), c2.to_ubuntu());
+    }
+
+    #[rstest]
+    #[case("24.01", 2024)]
+    #[case("4.01", 2004)]
+    #[case("023.12.2", 2023)]
+    fn test_year_ubuntu_calver(#[case] version: &str, #[case] expected: u32) {
+        let c = Calver::try_from_ubuntu(version).unwrap();
+        assert_eq!(c.year(), expected);
     }
 }
@@ -1,25 +1,26 @@
 use aws_config::BehaviorVersion;
 use aws_smithy_types_convert::date_time::DateTimeExt;
-use bnacore::aws::get_aws_parameter_value;
+use bnacore::{
+    aws::{get_aws_parameter_value, s3::fetch_s3_object_as_bytes},
+    scorecard::overall::{OverallScores, REQUIRED_SCORE_IDS},
+};
 use bnalambdas::{
-    authenticate_service_account, update_pipeline, AnalysisParameters, BNAPipeline, Context,
+    analysis_span, authenticate_service_account, fargate_cost, http_client, normalize_place,
+    run_blocking, update_pipeline, AnalysisParameters, BNAPipeline, BNAPipelineStep, Context,
     Fargate, AWSS3,
 };
-use csv::ReaderBuilder;
-use heck::ToTitleCase;
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
-use reqwest::blocking::Client;
+use reqwest::{
+    blocking::{Client, Response},
+    StatusCode,
+};
 use rust_decimal::Decimal;
-use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
 use simple_error::SimpleError;
-use std::{collections::HashMap, io::Write};
 use time::OffsetDateTime;
-use tracing::info;
+use tracing::{error, info, Instrument};
 use uuid::Uuid;
 
-const OVERALL_SCORES_COUNT: usize = 23;
-
 #[derive(Deserialize)]
 struct TaskInput {
     analysis_parameters: AnalysisParameters,
@@ -28,101 +29,6 @@ struct TaskInput {
     fargate: Fargate,
 }
 
-#[derive(Deserialize, Clone)]
-struct OverallScore {
-    pub score_id: String,
-    pub score_normalized: Option<f64>,
-}
-
-#[derive(Deserialize)]
-struct OverallScores(HashMap<String, OverallScore>);
-
-impl OverallScores {
-    /// Create an empty OverallScores.
-    pub fn new() -> Self {
-        OverallScores(HashMap::with_capacity(OVERALL_SCORES_COUNT))
-    }
-
-    /// Retrieve an OverallScore item by id.
-    fn get_overall_score(&self, score_id: &str) -> Option<OverallScore> {
-        self.0.get(score_id).cloned()
-    }
-
-    /// Retrieve the normalized score of an OverallScore item by id.
-    fn get_normalized_score(&self, score_id: &str) -> Option<f64> {
-        self.get_overall_score(score_id)
-            .and_then(|s| s.score_normalized)
-    }
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNASummary {
-    pub bna_uuid: Uuid,
-    pub version: String,
-    pub city_id: Uuid,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAInfrastructure {
-    pub low_stress_miles: Option<f64>,
-    pub high_stress_miles: Option<f64>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNARecreation {
-    pub community_centers: Option<f64>,
-    pub parks: Option<f64>,
-    pub recreation_trails: Option<f64>,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAOpportunity {
-    pub employment: Option<f64>,
-    pub higher_education: Option<f64>,
-    pub k12_education: Option<f64>,
-    pub technical_vocational_college: Option<f64>,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNACoreServices {
-    pub dentists: Option<f64>,
-    pub doctors: Option<f64>,
-    pub grocery: Option<f64>,
-    pub hospitals: Option<f64>,
-    pub pharmacies: Option<f64>,
-    pub social_services: Option<f64>,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAPeople {
-    pub score: Option<f64>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNARetail {
-    pub score: Option<f64>,
-}
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNATransit {
-    pub score: Option<f64>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAPost {
-    pub core_services: BNACoreServices,
-    pub people: BNAPeople,
-    pub retail: BNARetail,
-    pub transit: BNATransit,
-    pub infrastructure: BNAInfrastructure,
-    pub opportunity: BNAOpportunity,
-    pub recreation: BNARecreation,
-    pub summary: BNASummary,
-}
-
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct City {
     pub id: Option<Uuid>,
@@ -138,6 +44,81 @@ pub struct City {
     pub updated_at: Option<OffsetDateTime>,
 }
 
+/// Census information of a city, derived from the results of an analysis.
+#[derive(Serialize, Debug, PartialEq)]
+struct CityPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub population: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fips_code: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl CityPatch {
+    /// Prepare the census update of an existing city.
+    ///
+    /// The coordinates are not part of the results, therefore the ones of the
+    /// city are kept. A FIPS code of `"0"` means that there is none.
+    fn new(city: &City, overall_scores: &OverallScores, fips_code: Option<&str>) -> Self {
+        Self {
+            population: overall_scores.population(),
+            fips_code: fips_code
+                .filter(|fips_code| *fips_code != "0")
+                .map(str::to_string),
+            latitude: city.latitude,
+            longitude: city.longitude,
+        }
+    }
+}
+
+/// Outcome of a city lookup via the BNA API.
+#[derive(Debug)]
+enum CityLookup {
+    /// The city exists.
+    Found(Box<City>),
+    /// The city does not exist yet.
+    NotFound,
+    /// The lookup failed with an unexpected status.
+    Error(StatusCode),
+}
+
+impl CityLookup {
+    /// Classify the response of a city lookup.
+    ///
+    /// Only a `200 OK` carries a city. Any other status, including the other
+    /// successful ones and the redirections which were not followed, is an
+    /// error, except for `404 Not Found`.
+    fn from_response(response: Response) -> Result<Self, reqwest::Error> {
+        match response.status() {
+            StatusCode::OK => Ok(CityLookup::Found(Box::new(response.json::<City>()?))),
+            StatusCode::NOT_FOUND => Ok(CityLookup::NotFound),
+            status => Ok(CityLookup::Error(status)),
+        }
+    }
+}
+
+/// Look up a city via the BNA API.
+fn lookup_city(client: &Client, url: &str) -> Result<CityLookup, reqwest::Error> {
+    let response = client.get(url).send()?;
+    CityLookup::from_response(response)
+}
+
+/// Prepare the pipeline update recording the saved results.
+fn pipeline_update(
+    state_machine_id: Uuid,
+    cost: Option<Decimal>,
+    start_time: OffsetDateTime,
+    end_time: Option<OffsetDateTime>,
+) -> BNAPipeline {
+    BNAPipeline::update(state_machine_id)
+        .cost(cost)
+        .end_time(end_time)
+        .start_time(start_time)
+        .step(BNAPipelineStep::Save)
+        .build()
+}
+
 async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     // Read the task inputs.
     info!("Reading input...");
@@ -183,7 +164,15 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
 
     // Parse the results.
     info!("Parse the results...");
-    let overall_scores = parse_overall_scores(buffer.as_slice())?;
+    let overall_scores = OverallScores::from_reader(buffer.as_slice())?;
+    let missing = overall_scores.missing(&REQUIRED_SCORE_IDS);
+    if !missing.is_empty() {
+        let missing = missing.join(", ");
+        error!(missing, "the results are missing required scores");
+        return Err(Box::new(SimpleError::new(format!(
+            "the results at {scores_csv} are missing required scores: {missing}"
+        ))));
+    }
 
     // Query city.
     info!("Check for existing city...");
@@ -192,25 +181,21 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
         Some(region) => region.to_owned(),
         None => country.to_owned(),
     };
-    let name = &analysis_parameters.city;
+    let place = normalize_place(country, &region, &analysis_parameters.city);
     let cities_url = format!("{api_hostname}/cities");
     let get_cities_url = format!(
         "{cities_url}/{}/{}/{}",
-        country.to_title_case(),
-        region.to_title_case(),
-        name.to_title_case()
+        place.country, place.region, place.city
     );
-    info!("endpoint: {}", get_cities_url);
-    let client = Client::new();
-    let r = client.get(&get_cities_url).send()?;
-    let city: Option<City> = match r.status().as_u16() {
-        x if x < 400 => Some(r.json::<City>()?),
-        404 => None,
-        _ => {
+    info!(endpoint = get_cities_url, "querying cities");
+    let client = http_client();
+    let url = get_cities_url.clone();
+    let city: Option<City> = match run_blocking(move || lookup_city(client, &url)).await? {
+        CityLookup::Found(city) => Some(*city),
+        CityLookup::NotFound => None,
+        CityLookup::Error(status) => {
             return Err(Box::new(SimpleError::new(format!(
-                "cannot retrieve city at {get_cities_url}: {} {}",
-                r.status(),
-                r.status().as_str()
+                "cannot retrieve city at {get_cities_url}: {status}"
             ))))
         }
     };
@@ -222,29 +207,48 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     if let Some(city) = city {
         info!("The city exists, update the population...");
         city_id = city.id.unwrap();
-        // TODO: Patch city census.
+        let patch = CityPatch::new(
+            &city,
+            &overall_scores,
+            analysis_parameters.fips_code.as_deref(),
+        );
+        info!(endpoint = get_cities_url, ?patch, "patching city census");
+        let token = auth.access_token.clone();
+        run_blocking(move || {
+            client
+                .patch(&get_cities_url)
+                .bearer_auth(token)
+                .json(&patch)
+                .send()?
+                .error_for_status()
+        })
+        .await?;
     } else {
         info!("Create a new city...");
         // Create the city.
         let c = City {
-            country: country.clone().to_title_case(),
-            state: region.clone().to_title_case(),
-            name: name.clone().to_title_case(),
+            country: place.country,
+            state: place.region,
+            name: place.city,
             ..Default::default()
         };
-        let city = client
-            .post(cities_url)
-            .bearer_auth(auth.access_token.clone())
-            .json(&c)
-            .send()?
-            .error_for_status()?
-            .json::<City>()?;
+        let token = auth.access_token.clone();
+        let city = run_blocking(move || {
+            client
+                .post(cities_url)
+                .bearer_auth(token)
+                .json(&c)
+                .send()?
+                .error_for_status()?
+                .json::<City>()
+        })
+        .await?;
         city_id = city.id.unwrap();
     }
 
     // Convert the overall scores to a BNAPost struct.
-    let version = aws_s3.get_version();
-    let bna_post = scores_to_bnapost(overall_scores, version, city_id);
+    let version = aws_s3.get_version()?;
+    let bna_post = overall_scores.to_bna_post(version, city_id);
 
     // Prepare API URLs.
     let bnas_url = format!("{api_hostname}/ratings");
@@ -252,15 +256,20 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     // Post a new entry via the API.
     info!("Post a new BNA entry via the API...");
     info!("New entry: {:?}", &bna_post);
-    Client::new()
-        .post(&bnas_url)
-        .bearer_auth(auth.access_token.clone())
-        .json(&bna_post)
-        .send()?
-        .error_for_status()?;
+    let url = bnas_url.clone();
+    let token = auth.access_token.clone();
+    run_blocking(move || {
+        client
+            .post(&url)
+            .bearer_auth(token)
+            .json(&bna_post)
+            .send()?
+            .error_for_status()
+    })
+    .await?;
 
     // Compute the time it took to run the fargate task.
-    info!("describing fargate task {}", fargate.task_arn);
+    info!("describing fargate task");
     let describe_tasks = ecs_client
         .describe_tasks()
         .cluster(fargate.ecs_cluster_arn.clone())
@@ -272,123 +281,35 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
         .started_at()
         .expect("the task must have started at this point");
     let stopped_at = task_info
-        .started_at()
+        .stopped_at()
         .expect("the task must have stopped at this point");
-    let started_secs = started_at.secs();
-    let stopped_secs = stopped_at.secs();
-    let elapsed = (stopped_secs - started_secs) / 1000;
 
-    // Compute the price.
-    const FARGATE_COST_PER_SEC: Decimal = dec!(0.00228333333333);
-    let elapsed_decimal: Decimal = elapsed.into();
-    let cost = elapsed_decimal.checked_mul(FARGATE_COST_PER_SEC);
-
-    // TODO(rgreinho): Update the pipeline status when the new state will be available.
-    // Update the pipeline status.
-    info!("updating pipeline...");
-    let patch_url = format!("{bnas_url}/analysis/{state_machine_id}");
     let start_time = started_at
         .to_time()
         .expect("a valid start time is expected");
     let end_time = stopped_at.to_time().ok();
-    let pipeline = BNAPipeline {
-        cost,
-        end_time,
-        start_time,
-        state_machine_id,
-        step: Some("Setup".to_string()),
-        ..Default::default()
-    };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
 
-    Ok(())
-}
-
-fn parse_overall_scores(data: &[u8]) -> Result<OverallScores, Error> {
-    let mut overall_scores = OverallScores::new();
-    let mut rdr = ReaderBuilder::new().flexible(true).from_reader(data);
-    for result in rdr.deserialize() {
-        let score: OverallScore = result?;
-        overall_scores.0.insert(score.score_id.clone(), score);
-    }
-    Ok(overall_scores)
-}
+    // Compute the price.
+    let task_size = |size: Option<&str>, name: &str| {
+        size.and_then(|size| size.parse::<u32>().ok())
+            .ok_or_else(|| {
+                SimpleError::new(format!(
+                    "invalid {name} for task {}: {size:?}",
+                    fargate.task_arn
+                ))
+            })
+    };
+    let task_cpu = task_size(task_info.cpu(), "CPU")?;
+    let task_mem_mb = task_size(task_info.memory(), "memory")?;
+    let cost = end_time.map(|end_time| fargate_cost(start_time, end_time, task_cpu, task_mem_mb));
 
-fn scores_to_bnapost(overall_scores: OverallScores, version: String, city_id: Uuid) -> BNAPost {
-    BNAPost {
-        core_services: BNACoreServices {
-            dentists: overall_scores.get_normalized_score("core_services_dentists"),
-            doctors: overall_scores.get_normalized_score("core_services_doctors"),
-            grocery: overall_scores.get_normalized_score("core_services_grocery"),
-            hospitals: overall_scores.get_normalized_score("core_services_hospitals"),
-            pharmacies: overall_scores.get_normalized_score("core_services_pharmacies"),
-            social_services: overall_scores.get_normalized_score("core_services_social_services"),
-            score: overall_scores
-                .get_normalized_score("core_services")
-                .unwrap_or_default(),
-        },
-        people: BNAPeople {
-            score: overall_scores.get_normalized_score("people"),
-        },
-        retail: BNARetail {
-            score: overall_scores.get_normalized_score("retail"),
-        },
-        transit: BNATransit {
-            score: overall_scores.get_normalized_score("transit"),
-        },
-        infrastructure: BNAInfrastructure {
-            low_stress_miles: overall_scores.get_normalized_score("total_miles_low_stress"),
-            high_stress_miles: overall_scores.get_normalized_score("total_miles_high_stress"),
-        },
-        opportunity: BNAOpportunity {
-            employment: overall_scores.get_normalized_score("opportunity_employment"),
-            higher_education: overall_scores.get_normalized_score("opportunity_higher_education"),
-            k12_education: overall_scores.get_normalized_score("opportunity_k12_education"),
-            technical_vocational_college: overall_scores
-                .get_normalized_score("opportunity_technical_vocational_college"),
-            score: overall_scores
-                .get_normalized_score("opportunity")
-                .unwrap_or_default(),
-        },
-        recreation: BNARecreation {
-            community_centers: overall_scores.get_normalized_score("recreation_community_centers"),
-            parks: overall_scores.get_normalized_score("recreation_parks"),
-            recreation_trails: overall_scores.get_normalized_score("recreation_trails"),
-            score: overall_scores
-                .get_normalized_score("recreation_trails")
-                .unwrap_or_default(),
-        },
-        summary: BNASummary {
-            bna_uuid: Uuid::new_v4(),
-            version,
-            city_id,
-            score: 0.0,
-        },
-    }
-}
+    // Update the pipeline status.
+    info!("updating pipeline...");
+    let patch_url = format!("{bnas_url}/analysis/{state_machine_id}");
+    let pipeline = pipeline_update(state_machine_id, cost, start_time, end_time);
+    update_pipeline(client, &patch_url, &auth, &pipeline).await?;
 
-async fn fetch_s3_object_as_bytes(
-    client: &aws_sdk_s3::Client,
-    bucket: &str,
-    key: &str,
-) -> std::io::Result<Vec<u8>> {
-    let mut object = client
-        .get_object()
-        .bucket(bucket)
-        .key(key)
-        .send()
-        .await
-        .map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("AWS S3 operation error: {e}"),
-            )
-        })?;
-    let mut buffer: Vec<u8> = Vec::new();
-    while let Some(bytes) = object.body.try_next().await? {
-        buffer.write_all(&bytes)?;
-    }
-    Ok(buffer)
+    Ok(())
 }
 
 #[tokio::main]
@@ -401,7 +322,13 @@ async fn main() -> Result<(), Error> {
         .without_time()
         .init();
 
-    run(service_fn(function_handler)).await.map_err(|e| {
+    run(service_fn(|event: LambdaEvent<TaskInput>| {
+        let span = analysis_span(event.payload.context.id, &event.payload.analysis_parameters);
+        span.record("task_arn", &event.payload.fargate.task_arn);
+        function_handler(event).instrument(span)
+    }))
+    .await
+    .map_err(|e| {
         info!("{e}");
         e
     })
@@ -411,8 +338,98 @@ async fn main() -> Result<(), Error> {
 mod tests {
 
     use super::*;
+    use rstest::rstest;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
     // use bnalambdas::AuthResponse;
 
+    #[rstest]
+    #[case::found(200, Some("Santa Rosa"))]
+    #[case::not_found(404, None)]
+    #[case::forbidden(403, None)]
+    #[case::no_content(204, None)]
+    #[tokio::test]
+    async fn test_lookup_city(#[case] status: u16, #[case] expected_name: Option<&str>) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cities/Usa/New%20Mexico/Santa%20Rosa"))
+            .respond_with(
+                ResponseTemplate::new(status).set_body_json(serde_json::json!({
+                    "id": "5e2b2d4c-3e6a-4c7a-9d5b-2f9d0c3b1a8e",
+                    "country": "Usa",
+                    "state": "New Mexico",
+                    "name": "Santa Rosa"
+                })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/cities/Usa/New Mexico/Santa Rosa", server.uri());
+        let lookup = tokio::task::spawn_blocking(move || lookup_city(&Client::new(), &url))
+            .await
+            .unwrap()
+            .unwrap();
+        match (lookup, expected_name) {
+            (CityLookup::Found(city), Some(name)) => assert_eq!(city.name, name),
+            (CityLookup::NotFound, None) => assert_eq!(status, 404),
+            (CityLookup::Error(code), None) => assert_eq!(code.as_u16(), status),
+            (lookup, _) => panic!("unexpected lookup outcome for {status}: {lookup:?}"),
+        }
+    }
+
+    #[test]
+    fn test_pipeline_update_step() {
+        let now = OffsetDateTime::now_utc();
+        let pipeline = pipeline_update(Uuid::new_v4(), None, now, Some(now));
+        let step = pipeline.step.unwrap();
+        assert_eq!(step, BNAPipelineStep::Save);
+        assert!(BNAPipelineStep::Analysis.can_transition_to(&step));
+    }
+
+    #[test]
+    fn test_city_patch() {
+        let data = r#"id,score_id,score_original,score_normalized,human_explanation
+20,overall_score,0.0893,8.9300,
+21,population_total,2960.0000,,Total population of boundary"#;
+        let overall_scores = OverallScores::from_reader(data.as_bytes()).unwrap();
+        let city = City {
+            id: Some(Uuid::new_v4()),
+            country: "Usa".to_string(),
+            state: "New Mexico".to_string(),
+            name: "Santa Rosa".to_string(),
+            latitude: Some(34.9387),
+            longitude: Some(-104.6825),
+            ..Default::default()
+        };
+
+        let patch = CityPatch::new(&city, &overall_scores, Some("3570670"));
+        assert_eq!(
+            patch,
+            CityPatch {
+                population: Some(2960),
+                fips_code: Some("3570670".to_string()),
+                latitude: Some(34.9387),
+                longitude: Some(-104.6825),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&patch).unwrap(),
+            serde_json::json!({
+                "population": 2960,
+                "fips_code": "3570670",
+                "latitude": 34.9387,
+                "longitude": -104.6825
+            })
+        );
+
+        // A FIPS code of "0" is not sent.
+        let patch = CityPatch::new(&city, &overall_scores, Some("0"));
+        assert_eq!(patch.fips_code, None);
+    }
+
     #[test]
     fn test_input_deserialization() {
         let json_input = r#"{
@@ -452,35 +469,6 @@ mod tests {
         let _deserialized = serde_json::from_str::<TaskInput>(json_input).unwrap();
     }
 
-    #[test]
-    fn test_parse_overallscores() {
-        let data = r#"id,score_id,score_original,score_normalized,human_explanation
-1,people,0.1917,19.1700,"On average, census blocks in the neighborhood received this population score."
-2,opportunity_employment,0.0826,8.2600,"On average, census blocks in the neighborhood received this employment score."
-3,opportunity_k12_education,0.0831,8.3100,"On average, census blocks in the neighborhood received this K12 schools score."
-4,opportunity_technical_vocational_college,0.0000,0.0000,"On average, census blocks in the neighborhood received this tech/vocational colleges score."
-5,opportunity_higher_education,0.0000,0.0000,"On average, census blocks in the neighborhood received this universities score."
-6,opportunity,0.0829,8.2900,
-7,core_services_doctors,0.0000,0.0000,"On average, census blocks in the neighborhood received this doctors score."
-8,core_services_dentists,0.0000,0.0000,"On average, census blocks in the neighborhood received this dentists score."
-9,core_services_hospitals,0.0518,5.1800,"On average, census blocks in the neighborhood received this hospital score."
-10,core_services_pharmacies,0.0000,0.0000,"On average, census blocks in the neighborhood received this pharmacies score."
-11,core_services_grocery,0.0169,1.6900,"On average, census blocks in the neighborhood received this grocery score."
-12,core_services_social_services,0.0000,0.0000,"On average, census blocks in the neighborhood received this social services score."
-13,core_services,0.0324,3.2400,
-14,retail,0.0000,0.0000,"On average, census blocks in the neighborhood received this retail score."
-15,recreation_parks,0.0713,7.1300,"On average, census blocks in the neighborhood received this parks score."
-16,recreation_trails,0.0000,0.0000,"On average, census blocks in the neighborhood received this trails score."
-17,recreation_community_centers,0.0000,0.0000,"On average, census blocks in the neighborhood received this community centers score."
-18,recreation,0.0713,7.1300,
-19,transit,0.0000,0.0000,"On average, census blocks in the neighborhood received this transit score."
-20,overall_score,0.0893,8.9300,
-21,population_total,2960.0000,,Total population of boundary
-22,total_miles_low_stress,9.3090,9.3000,Total low-stress miles
-23,total_miles_high_stress,64.5092,64.5000,Total high-stress miles"#;
-        let _scores = parse_overall_scores(data.as_bytes()).unwrap();
-    }
-
     // #[test]
     // fn test_post() {
     //     let data = r#"id,score_id,score_original,score_normalized,human_explanation
@@ -507,12 +495,12 @@ mod tests {
     // 21,population_total,2960.0000,,Total population of boundary
     // 22,total_miles_low_stress,9.3090,9.3000,Total low-stress miles
     // 23,total_miles_high_stress,64.5092,64.5000,Total high-stress miles"#;
-    //     let overall_scores = parse_overall_scores(data.as_bytes()).unwrap();
+    //     let overall_scores = OverallScores::from_reader(data.as_bytes()).unwrap();
 
     //     // Convert the overall scores to a BNAPost struct.
     //     let version = String::from("24.05");
     //     let city_id = Uuid::new_v4();
-    //     let bna_post = scores_to_bnapost(overall_scores, version, city_id);
+    //     let bna_post = overall_scores.to_bna_post(version, city_id);
     //     dbg!(&bna_post);
     //     let s = serde_json::to_string(&bna_post);
     //     dbg!(s);
@@ -542,20 +530,6 @@ mod tests {
     //     .unwrap();
     // }
 
-    // #[tokio::test]
-    // async fn test_fetch_s3_object() {
-    //     let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    //     let client = aws_sdk_s3::Client::new(&config);
-    //     let buffer = fetch_s3_object_as_bytes(
-    //         client,
-    //         "brokenspoke-analyzer",
-    //         "spain/valencia/valencia/24.4/neighborhood_overall_scores.csv",
-    //     )
-    //     .await
-    //     .unwrap();
-    //     assert!(buffer.len() > 0)
-    // }
-
     // #[test]
     // fn test_post_cities() {
     //     let country = String::from("United States");
@@ -609,11 +583,11 @@ mod tests {
     //         .started_at()
     //         .expect("the task must have started at this point");
     //     let stopped_at = task_info
-    //         .started_at()
+    //         .stopped_at()
     //         .expect("the task must have stopped at this point");
     //     let started_secs = started_at.secs();
     //     let stopped_secs = stopped_at.secs();
-    //     let elapsed = (stopped_secs - started_secs) / 1000;
+    //     let elapsed = stopped_secs - started_secs;
     //     dbg!(task_info);
     //     dbg!(elapsed);
     // }
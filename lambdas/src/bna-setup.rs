@@ -1,16 +1,16 @@
-use bnacore::{
-    aws::{get_aws_parameter_value, get_aws_secrets_value},
-    neon,
-};
+use bnacore::{aws::get_aws_parameter_value, neon};
 use bnalambdas::{
-    authenticate_service_account, update_pipeline, AnalysisParameters, BNAPipeline, Context,
+    analysis_span, authenticate_service_account, http_client, update_pipeline, AnalysisParameters,
+    BNAPipeline, BNAPipelineStep, Context,
 };
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use serde::{Deserialize, Serialize};
 use simple_error::SimpleError;
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, Instrument, Span};
 
 const NEON_MAX_BRANCHES: usize = 20;
+const NEON_OPERATIONS_TIMEOUT: Duration = Duration::from_secs(120);
 
 #[derive(Deserialize)]
 struct TaskInput {
@@ -51,36 +51,17 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, E
     // Update the pipeline status.
     info!("updating pipeline...");
     let patch_url = format!("{url}/{state_machine_id}");
-    let pipeline = BNAPipeline {
-        state_machine_id,
-        step: Some("Setup".to_string()),
-        ..Default::default()
-    };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
+    let client = http_client();
+    let pipeline = BNAPipeline::update(state_machine_id)
+        .step(BNAPipelineStep::Setup)
+        .build();
+    update_pipeline(client, &patch_url, &auth, &pipeline).await?;
 
     // Create the Neon HTTP client.
     info!("Creating Neon client...");
-    let api_key = get_aws_secrets_value("NEON_API_KEY", "NEON_API_KEY").await?;
-    let project_id = get_aws_parameter_value("NEON_BROKENSPOKE_ANALYZER_PROJECT").await?;
-    let neon = neon::Client::new(&api_key, &project_id)?;
-
-    // Query neon API and check whether we can create a branch or not.
-    info!("Checking database branch capacity...");
-    let branches = neon.get_branches().await?;
-
-    // Not enough capacity to proceed. Back into the queue.
-    if branches.branches.len() >= (NEON_MAX_BRANCHES + 1) {
-        return Err(Box::new(SimpleError::new(format!(
-            "Not enough capacity to proceed ({} branches). Back into the queue",
-            branches.branches.len()
-        ))));
-    }
-    info!(
-        "There is(are) {} active branch(es).",
-        branches.branches.len()
-    );
+    let neon = neon::Client::from_aws().await?;
 
-    // Prepare new branch.
+    // Prepare the branch name.
     let mut branch_name = format!(
         "{}-{}-",
         analysis_parameters.country, analysis_parameters.city,
@@ -90,45 +71,78 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, E
         None => branch_name.push_str(&analysis_parameters.country),
     };
     branch_name = branch_name.replace(' ', "-");
-    if branches
-        .branches
-        .into_iter()
-        .filter_map(|b| b.branch.name)
-        .any(|b| branch_name == b)
+
+    // Create the database branch, or reuse it if this step is being retried.
+    let neon_branch = ensure_branch(&neon, &branch_name, NEON_OPERATIONS_TIMEOUT).await?;
+    Span::current().record("branch_id", &neon_branch.branch_id);
+
+    // Update the pipeline status.
+    let pipeline = BNAPipeline::update(state_machine_id).build();
+    update_pipeline(client, &patch_url, &auth, &pipeline).await?;
+
+    // Return the ID of the created database branch.
+    Ok(TaskOutput { neon: neon_branch })
+}
+
+/// Create the database branch of an analysis.
+///
+/// If a branch with the same name already exists, it was created by a previous
+/// attempt of this step, and it is returned instead of creating a new one.
+/// Therefore the capacity is only checked before creating a branch.
+async fn ensure_branch(
+    neon: &neon::Client,
+    branch_name: &str,
+    timeout: Duration,
+) -> Result<Neon, Error> {
+    // Query neon API and check whether the branch already exists.
+    info!("Looking for an existing database branch...");
+    let branches = neon.get_all_branches().await?;
+    if let Some(branch_id) = branches
+        .find_by_name(branch_name)
+        .and_then(|branch| branch.id.clone())
     {
-        return Err(Box::new(SimpleError::new(
-            "a branch with the same name already exists",
-        )));
+        info!(branch_name, branch_id, "reusing the existing branch");
+        let endpoints = neon.get_branch_endpoints(&branch_id).await?;
+        let host = endpoints
+            .into_iter()
+            .find_map(|endpoint| endpoint.host)
+            .ok_or_else(|| {
+                SimpleError::new(format!(
+                    "the branch `{branch_id}` does not have an endpoint"
+                ))
+            })?;
+        return Ok(Neon { branch_id, host });
     }
 
+    // Not enough capacity to proceed. Back into the queue.
+    let active_branches = branches.branches.len();
+    if active_branches >= (NEON_MAX_BRANCHES + 1) {
+        return Err(Box::new(SimpleError::new(format!(
+            "Not enough capacity to proceed ({active_branches} branches). Back into the queue",
+        ))));
+    }
+    info!(active_branches, "database branch capacity available");
+
     // Create the neon branch.
-    info!("Creating branch {}...", branch_name);
-    let create_branch_response = neon.create_branch(&branch_name).await?;
+    info!(branch_name, "creating branch");
+    let create_branch_response = neon.create_branch(branch_name).await?;
     info!("{:#?}", create_branch_response);
 
-    let neon_branch_id = create_branch_response.branch.id.unwrap();
-    let neon_host = create_branch_response
+    // Wait for the branch and its endpoint to be ready.
+    info!("Waiting for the branch operations to finish...");
+    neon.wait_for_operations(&create_branch_response.operations, timeout)
+        .await?;
+
+    let branch_id = create_branch_response.branch.id.unwrap();
+    let host = create_branch_response
         .endpoints
         .first()
         .unwrap()
         .host
         .clone()
         .unwrap();
-
-    // Update the pipeline status.
-    let pipeline = BNAPipeline {
-        state_machine_id,
-        ..Default::default()
-    };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
-
-    // Return the ID of the created database branch.
-    Ok(TaskOutput {
-        neon: Neon {
-            branch_id: neon_branch_id,
-            host: neon_host,
-        },
-    })
+    info!(host, "database branch ready");
+    Ok(Neon { branch_id, host })
 }
 
 #[tokio::main]
@@ -141,7 +155,12 @@ async fn main() -> Result<(), Error> {
         .without_time()
         .init();
 
-    run(service_fn(function_handler)).await.map_err(|e| {
+    run(service_fn(|event: LambdaEvent<TaskInput>| {
+        let span = analysis_span(event.payload.context.id, &event.payload.analysis_parameters);
+        function_handler(event).instrument(span)
+    }))
+    .await
+    .map_err(|e| {
         info!("{e}");
         e
     })
@@ -150,6 +169,10 @@ async fn main() -> Result<(), Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
 
     #[test]
     fn test_input_deserialization() {
@@ -181,4 +204,48 @@ mod tests {
         }"#;
         let _deserialized = serde_json::from_str::<TaskInput>(json_input).unwrap();
     }
+
+    #[tokio::test]
+    async fn test_ensure_branch_reuses_existing_branch() {
+        let server = MockServer::start().await;
+        // The project is already at capacity, which must not prevent reusing
+        // the branch.
+        let mut branches = (0..NEON_MAX_BRANCHES)
+            .map(|i| format!(r#"{{"id": "br-{i}", "name": "branch-{i}"}}"#))
+            .collect::<Vec<String>>();
+        branches
+            .push(r#"{"id": "br-odd-dream-88611736", "name": "usa-santa-rosa-new-mexico"}"#.into());
+        Mock::given(method("GET"))
+            .and(path("/patient-smoke-782429/branches"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                format!(r#"{{"branches": [{}]}}"#, branches.join(",")),
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/patient-smoke-782429/branches/br-odd-dream-88611736/endpoints",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"endpoints": [{"host": "ep-small-frog-123456.us-west-2.aws.neon.tech", "type": "read_write"}]}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let neon =
+            neon::Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri())
+                .unwrap();
+        let branch = ensure_branch(&neon, "usa-santa-rosa-new-mexico", NEON_OPERATIONS_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(branch.branch_id, "br-odd-dream-88611736");
+        assert_eq!(branch.host, "ep-small-frog-123456.us-west-2.aws.neon.tech");
+    }
 }
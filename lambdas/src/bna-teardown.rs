@@ -1,15 +1,23 @@
-use bnacore::aws::get_aws_parameter_value;
+use bnacore::{
+    aws::get_aws_parameter_value,
+    neon::{self, NeonError},
+};
 use bnalambdas::{
-    authenticate_service_account, update_pipeline, AnalysisParameters, BNAPipeline, Context,
+    analysis_span, authenticate_service_account, http_client, update_pipeline, AnalysisParameters,
+    BNAPipeline, BNAPipelineStep, Context,
 };
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tracing::{info, warn, Instrument, Span};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct TaskInput {
     analysis_parameters: AnalysisParameters,
     context: Context,
+    /// Output of the setup step, describing the database of the analysis.
+    #[serde(default)]
+    setup: Option<Setup>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -39,17 +47,51 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     let state_machine_id = state_machine_context.id;
 
     // Update the pipeline status.
+    info!("updating pipeline");
     let patch_url = format!("{url}/{state_machine_id}");
-    let pipeline = BNAPipeline {
-        state_machine_id,
-        step: Some("Cleanup".to_string()),
-        ..Default::default()
+    let client = http_client();
+    let pipeline = BNAPipeline::update(state_machine_id)
+        .step(BNAPipelineStep::Cleanup)
+        .build();
+    update_pipeline(client, &patch_url, &auth, &pipeline).await?;
+
+    // Delete the database branch.
+    let Some(setup) = &event.payload.setup else {
+        warn!("no database branch to delete");
+        return Ok(());
     };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
+    Span::current().record("branch_id", &setup.neon.branch_id);
+    info!("deleting database branch");
+    let neon = neon::Client::from_aws().await?;
+    delete_branch(&neon, &setup.neon.branch_id).await?;
+
+    // Record that the resources were released.
+    let pipeline = BNAPipeline::update(state_machine_id)
+        .torn_down(true)
+        .build();
+    update_pipeline(client, &patch_url, &auth, &pipeline).await?;
 
     Ok(())
 }
 
+/// Delete a database branch.
+///
+/// A branch which does not exist is considered deleted, for instance when this
+/// step is being retried.
+async fn delete_branch(neon: &neon::Client, branch_id: &str) -> Result<(), NeonError> {
+    match neon.delete_branch(branch_id).await {
+        Ok(_) => Ok(()),
+        Err(NeonError::Api {
+            status: StatusCode::NOT_FOUND,
+            ..
+        }) => {
+            info!("the database branch was already deleted");
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
@@ -60,7 +102,12 @@ async fn main() -> Result<(), Error> {
         .without_time()
         .init();
 
-    run(service_fn(function_handler)).await.map_err(|e| {
+    run(service_fn(|event: LambdaEvent<TaskInput>| {
+        let span = analysis_span(event.payload.context.id, &event.payload.analysis_parameters);
+        function_handler(event).instrument(span)
+    }))
+    .await
+    .map_err(|e| {
         info!("{e}");
         e
     })
@@ -72,6 +119,12 @@ mod tests {
     use bnalambdas::{Execution, State, StateMachine};
     use lambda_runtime::{Context, LambdaEvent};
     use uuid::Uuid;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    const BRANCH_PATH: &str = "/patient-smoke-782429/branches/br-odd-dream-88611736";
 
     #[tokio::test]
     async fn test_handler() {
@@ -102,6 +155,7 @@ mod tests {
                 },
                 id: Uuid::new_v4(),
             },
+            setup: None,
         };
         let _event = LambdaEvent { payload, context };
 
@@ -142,6 +196,82 @@ mod tests {
           }"#;
         let deserialized = serde_json::from_str::<TaskInput>(json_input).unwrap();
         assert_eq!(deserialized.analysis_parameters.city, "provincetown");
+        assert!(deserialized.setup.is_none());
         let _serialized = serde_json::to_string(&deserialized).unwrap();
     }
+
+    #[tokio::test]
+    async fn test_delete_branch() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path(BRANCH_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"branch": {"id": "br-odd-dream-88611736"}, "operations": []}"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let neon =
+            neon::Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri())
+                .unwrap();
+        delete_branch(&neon, "br-odd-dream-88611736").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_branch_already_deleted() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path(BRANCH_PATH))
+            .respond_with(ResponseTemplate::new(404).set_body_raw(
+                r#"{"code": "", "message": "branch not found"}"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let neon =
+            neon::Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri())
+                .unwrap();
+        delete_branch(&neon, "br-odd-dream-88611736").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_branch_already_deleted_plain_text() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path(BRANCH_PATH))
+            .respond_with(ResponseTemplate::new(404).set_body_string("404 page not found"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let neon =
+            neon::Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri())
+                .unwrap();
+        delete_branch(&neon, "br-odd-dream-88611736").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_branch_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path(BRANCH_PATH))
+            .respond_with(ResponseTemplate::new(423).set_body_raw(
+                r#"{"code": "", "message": "project already has running operations"}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let neon =
+            neon::Client::with_projects_url("api_key", "patient-smoke-782429", &server.uri())
+                .unwrap();
+        let err = delete_branch(&neon, "br-odd-dream-88611736")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, NeonError::Api { status, .. } if status == StatusCode::LOCKED));
+    }
 }
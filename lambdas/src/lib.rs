@@ -1,12 +1,52 @@
-use bnacore::aws::{get_aws_parameter_value, get_aws_secrets_value};
-use reqwest::blocking::Client;
+use bnacore::{
+    aws::{get_aws_parameter_value, get_aws_secrets, AWSError, SecretValue},
+    backoff_delay,
+    versioning::Calver,
+    HttpTimeouts,
+};
+use heck::ToTitleCase;
+use reqwest::{blocking::Client, StatusCode};
 use rust_decimal::prelude::*;
+use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use slug::slugify;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
 use time::OffsetDateTime;
+use tokio::sync::Mutex;
+use tracing::{field::Empty, info_span, Span};
 use uuid::Uuid;
 
 pub const BROKENSPOKE_ANALYZER_BUCKET: &str = "brokenspoke-analyzer";
 
+/// Maximum length of the slug identifying an analysis.
+pub const SLUG_LENGTH: usize = 71;
+
+/// Length of the short ID appended to the slug of an analysis.
+pub const SHORT_UUID_LENGTH: usize = 8;
+
+/// Margin before the expiration of a cached Cognito token, after which a new
+/// token gets requested.
+pub const TOKEN_EXPIRY_SKEW: Duration = Duration::from_secs(60);
+
+/// Fargate price per vCPU per hour, in USD (us-west-2, Linux/x86).
+pub const FARGATE_VCPU_HOUR_PRICE: Decimal = dec!(0.04048);
+
+/// Fargate price per GB of memory per hour, in USD (us-west-2, Linux/x86).
+pub const FARGATE_GB_HOUR_PRICE: Decimal = dec!(0.004445);
+
+/// Maximum number of retries when updating a pipeline.
+const UPDATE_PIPELINE_MAX_RETRIES: u32 = 3;
+
+/// Delay before the first retry when updating a pipeline, doubled after each
+/// attempt.
+const UPDATE_PIPELINE_BASE_DELAY: Duration = Duration::from_millis(200);
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AnalysisParameters {
     pub country: String,
@@ -68,9 +108,117 @@ impl AnalysisParameters {
             fips_code,
         }
     }
+
+    /// Generate the slug identifying the analysis.
+    ///
+    /// The slug is made of the country, the region and the city, and is
+    /// truncated to [`SLUG_LENGTH`] characters. If the region is missing, the
+    /// country is used instead, like in [`AnalysisParameters::sanitized`].
+    ///
+    /// ```
+    /// use bnalambdas::AnalysisParameters;
+    ///
+    /// let params = AnalysisParameters::with_region(
+    ///     "usa".to_string(),
+    ///     "santa rosa".to_string(),
+    ///     "new mexico".to_string(),
+    /// );
+    /// assert_eq!(params.slug(), "usa-new-mexico-santa-rosa");
+    /// ```
+    pub fn slug(&self) -> String {
+        let region = self.region.as_deref().unwrap_or(&self.country);
+        let mut slug = slugify(format!("{}-{}-{}", self.country, region, self.city));
+        slug.truncate(SLUG_LENGTH);
+        slug
+    }
+
+    /// Generate the slug identifying a specific run of the analysis.
+    ///
+    /// The slug is suffixed with the first [`SHORT_UUID_LENGTH`] characters of
+    /// the `id`, usually the ID of the state machine running the analysis.
+    ///
+    /// ```
+    /// use bnalambdas::AnalysisParameters;
+    /// use uuid::Uuid;
+    ///
+    /// let params = AnalysisParameters::simple("france".to_string(), "paris".to_string());
+    /// let id = Uuid::parse_str("9ff90cac-0cf5-4923-897f-4416df5e7328").unwrap();
+    /// assert_eq!(params.slug_with_id(id), "france-france-paris-9ff90cac");
+    /// ```
+    pub fn slug_with_id(&self, id: Uuid) -> String {
+        let mut short_id = id.to_string();
+        short_id.truncate(SHORT_UUID_LENGTH);
+        format!("{}-{short_id}", self.slug())
+    }
+
+    /// Rebuild the parameters from a slug, on a best-effort basis.
+    ///
+    /// A slug only contains lowercase ASCII words separated by dashes, so the
+    /// original case and accents are lost, and the boundaries between the
+    /// country, the region and the city are only known when each of them is a
+    /// single word. Therefore `None` is returned for any slug which is not
+    /// made of exactly 3 words, optionally followed by a short ID, like the
+    /// ones produced by [`AnalysisParameters::slug_with_id`]. A slug truncated
+    /// to [`SLUG_LENGTH`] characters yields a truncated city name.
+    ///
+    /// ```
+    /// use bnalambdas::AnalysisParameters;
+    ///
+    /// let params = AnalysisParameters::from_slug("belgium-flanders-antwerp").unwrap();
+    /// assert_eq!(params.region.as_deref(), Some("flanders"));
+    /// assert!(AnalysisParameters::from_slug("usa-new-mexico-santa-rosa").is_none());
+    /// ```
+    pub fn from_slug(slug: &str) -> Option<AnalysisParameters> {
+        let mut parts = slug.split('-').collect::<Vec<&str>>();
+        let has_short_id = parts.len() == 4
+            && parts[3].len() == SHORT_UUID_LENGTH
+            && parts[3].chars().all(|c| c.is_ascii_hexdigit());
+        if has_short_id {
+            parts.pop();
+        }
+        match parts.as_slice() {
+            [country, region, city] if parts.iter().all(|part| !part.is_empty()) => {
+                Some(AnalysisParameters::with_region(
+                    country.to_string(),
+                    city.to_string(),
+                    region.to_string(),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Validate the parameters.
+    ///
+    /// The country and the city must not be empty, and the FIPS code, unless it
+    /// is missing or `"0"`, must be made of 5 (county) or 7 (place) digits.
+    pub fn validate(&self) -> Result<(), bnacore::Error> {
+        if self.country.trim().is_empty() {
+            return Err(bnacore::Error::InvalidArgument(
+                "the country must not be empty".to_string(),
+            ));
+        }
+        if self.city.trim().is_empty() {
+            return Err(bnacore::Error::InvalidArgument(
+                "the city must not be empty".to_string(),
+            ));
+        }
+        if let Some(fips_code) = self.fips_code.as_deref() {
+            let is_valid = fips_code == "0"
+                || ([5, 7].contains(&fips_code.len())
+                    && fips_code.chars().all(|c| c.is_ascii_digit()));
+            if !is_valid {
+                return Err(bnacore::Error::InvalidArgument(format!(
+                    "invalid FIPS code `{fips_code}`: it must be made of 5 or 7 digits"
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BNAPipeline {
     pub cost: Option<Decimal>,
     pub end_time: Option<OffsetDateTime>,
@@ -79,32 +227,110 @@ pub struct BNAPipeline {
     pub result_posted: Option<bool>,
     pub s3_bucket: Option<String>,
     pub sqs_message: Option<String>,
-    pub start_time: OffsetDateTime,
+    pub start_time: Option<OffsetDateTime>,
     pub state_machine_id: Uuid,
     pub status: Option<String>,
-    pub step: Option<String>,
+    pub step: Option<BNAPipelineStep>,
     pub torn_down: Option<bool>,
 }
 
-impl Default for BNAPipeline {
-    fn default() -> Self {
-        Self {
-            cost: Default::default(),
-            end_time: Default::default(),
-            fargate_price_id: Default::default(),
-            fargate_task_arn: Default::default(),
-            result_posted: Default::default(),
-            s3_bucket: Default::default(),
-            sqs_message: Default::default(),
-            start_time: OffsetDateTime::now_utc(),
-            state_machine_id: Default::default(),
-            status: Default::default(),
-            step: Default::default(),
-            torn_down: Default::default(),
+impl BNAPipeline {
+    /// Start building an update of the pipeline run by the specified state
+    /// machine.
+    ///
+    /// Only the fields which are explicitly set are serialized, therefore a
+    /// patch does not overwrite the other fields of the pipeline, like its
+    /// start time.
+    ///
+    /// ```
+    /// use bnalambdas::{BNAPipeline, BNAPipelineStep};
+    /// use uuid::Uuid;
+    ///
+    /// let pipeline = BNAPipeline::update(Uuid::new_v4())
+    ///     .step(BNAPipelineStep::Setup)
+    ///     .build();
+    /// assert_eq!(pipeline.step, Some(BNAPipelineStep::Setup));
+    /// assert_eq!(pipeline.torn_down, None);
+    /// assert_eq!(pipeline.start_time, None);
+    /// ```
+    pub fn update(state_machine_id: Uuid) -> BNAPipelineBuilder {
+        BNAPipelineBuilder {
+            pipeline: BNAPipeline {
+                state_machine_id,
+                ..Default::default()
+            },
         }
     }
 }
 
+/// Build a [`BNAPipeline`] update, see [`BNAPipeline::update`].
+#[derive(Debug, Clone)]
+pub struct BNAPipelineBuilder {
+    pipeline: BNAPipeline,
+}
+
+impl BNAPipelineBuilder {
+    pub fn cost(mut self, cost: impl Into<Option<Decimal>>) -> Self {
+        self.pipeline.cost = cost.into();
+        self
+    }
+
+    pub fn end_time(mut self, end_time: impl Into<Option<OffsetDateTime>>) -> Self {
+        self.pipeline.end_time = end_time.into();
+        self
+    }
+
+    pub fn fargate_price_id(mut self, fargate_price_id: i32) -> Self {
+        self.pipeline.fargate_price_id = Some(fargate_price_id);
+        self
+    }
+
+    pub fn fargate_task_arn(mut self, fargate_task_arn: impl Into<String>) -> Self {
+        self.pipeline.fargate_task_arn = Some(fargate_task_arn.into());
+        self
+    }
+
+    pub fn result_posted(mut self, result_posted: bool) -> Self {
+        self.pipeline.result_posted = Some(result_posted);
+        self
+    }
+
+    pub fn s3_bucket(mut self, s3_bucket: impl Into<String>) -> Self {
+        self.pipeline.s3_bucket = Some(s3_bucket.into());
+        self
+    }
+
+    pub fn sqs_message(mut self, sqs_message: impl Into<String>) -> Self {
+        self.pipeline.sqs_message = Some(sqs_message.into());
+        self
+    }
+
+    pub fn start_time(mut self, start_time: OffsetDateTime) -> Self {
+        self.pipeline.start_time = Some(start_time);
+        self
+    }
+
+    pub fn status(mut self, status: impl Into<String>) -> Self {
+        self.pipeline.status = Some(status.into());
+        self
+    }
+
+    pub fn step(mut self, step: BNAPipelineStep) -> Self {
+        self.pipeline.step = Some(step);
+        self
+    }
+
+    pub fn torn_down(mut self, torn_down: bool) -> Self {
+        self.pipeline.torn_down = Some(torn_down);
+        self
+    }
+
+    /// Return the pipeline update.
+    pub fn build(self) -> BNAPipeline {
+        self.pipeline
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BNAPipelineStatus {
     Pending,
@@ -120,12 +346,39 @@ impl FromStr for BNAPipelineStatus {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Describe the steps of the BNA pipeline.
+///
+/// The steps are serialized with the exact names expected by the API, e.g.
+/// `"Analysis"`. They are declared, and therefore ordered, in the order they
+/// happen in the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum BNAPipelineStep {
-    Analysis,
-    Cleanup,
     Setup,
+    Analysis,
     Save,
+    Cleanup,
+}
+
+impl BNAPipelineStep {
+    /// Return the position of the step in the pipeline, starting at 0.
+    pub fn ordinal(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Return true if the pipeline can move from this step to the `next` one.
+    ///
+    /// A step can only be followed by itself, when it gets reported several
+    /// times, or by the step immediately after it in the pipeline.
+    ///
+    /// ```
+    /// use bnalambdas::BNAPipelineStep;
+    ///
+    /// assert!(BNAPipelineStep::Setup.can_transition_to(&BNAPipelineStep::Analysis));
+    /// assert!(!BNAPipelineStep::Setup.can_transition_to(&BNAPipelineStep::Cleanup));
+    /// ```
+    pub fn can_transition_to(&self, next: &BNAPipelineStep) -> bool {
+        next.ordinal() == self.ordinal() || next.ordinal() == self.ordinal() + 1
+    }
 }
 
 impl FromStr for BNAPipelineStep {
@@ -137,7 +390,7 @@ impl FromStr for BNAPipelineStep {
 }
 
 /// Define Cognito autnetication response.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AuthResponse {
     pub access_token: String,
     pub expires_in: u32,
@@ -150,15 +403,35 @@ pub struct AppClientCredentials {
     pub client_secret: String,
 }
 
+impl AppClientCredentials {
+    /// Read the app client credentials from the `client_id` and `client_secret`
+    /// keys of a secret.
+    pub fn from_secret(secret: &SecretValue) -> Result<Self, AWSError> {
+        #[derive(Deserialize)]
+        struct RawCredentials {
+            client_id: Option<String>,
+            client_secret: Option<String>,
+        }
+
+        let raw = secret.parse_secret_as::<RawCredentials>()?;
+        let missing_key = |key: &str| AWSError::SecretKeyNotFound {
+            secret_id: secret.name.clone(),
+            key: key.into(),
+        };
+        Ok(Self {
+            client_id: raw.client_id.ok_or_else(|| missing_key("client_id"))?,
+            client_secret: raw
+                .client_secret
+                .ok_or_else(|| missing_key("client_secret"))?,
+        })
+    }
+}
+
 /// Retrieve service account credentials.
 pub async fn get_service_account_credentials() -> Result<AppClientCredentials, bnacore::Error> {
     const SERVICE_ACCOUNT_CREDENTIALS: &str = "BROKENSPOKE_ANALYZER_SERVICE_ACCOUNT_CREDENTIALS";
-    let client_id = get_aws_secrets_value(SERVICE_ACCOUNT_CREDENTIALS, "client_id").await?;
-    let client_secret = get_aws_secrets_value(SERVICE_ACCOUNT_CREDENTIALS, "client_secret").await?;
-    Ok(AppClientCredentials {
-        client_id,
-        client_secret,
-    })
+    let secret = get_aws_secrets(SERVICE_ACCOUNT_CREDENTIALS).await?;
+    Ok(AppClientCredentials::from_secret(&secret)?)
 }
 
 pub async fn authenticate(
@@ -167,24 +440,238 @@ pub async fn authenticate(
     const COGNITO_HOSTNAME: &str = "BNA_COGNITO_HOSTNAME";
     let cognito_hostname = get_aws_parameter_value(COGNITO_HOSTNAME).await?;
     let token_endpoint = format!("{cognito_hostname}/oauth2/token");
-    Ok(Client::new()
-        .post(token_endpoint)
-        .form(&[
-            ("grant_type", "client_credentials"),
-            ("scope", "service_account/write"),
-        ])
-        .basic_auth(
-            credentials.client_id.clone(),
-            Some(credentials.client_secret.clone()),
-        )
-        .send()?
-        .error_for_status()?
-        .json::<AuthResponse>()?)
+    let client_id = credentials.client_id.clone();
+    let client_secret = credentials.client_secret.clone();
+
+    // The HTTP client is blocking, therefore it must not run on the async
+    // runtime.
+    let auth = tokio::task::spawn_blocking(move || {
+        http_client()
+            .post(token_endpoint)
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("scope", "service_account/write"),
+            ])
+            .basic_auth(client_id, Some(client_secret))
+            .send()?
+            .error_for_status()?
+            .json::<AuthResponse>()
+    })
+    .await
+    .map_err(|e| {
+        bnacore::Error::Internal(format!("cannot run the authentication request: {e}"))
+    })??;
+    Ok(auth)
 }
 
+/// Build the command passed to the brokenspoke-analyzer container.
+///
+/// The results are exported to `s3_dir` in `s3_bucket`. The region and the
+/// FIPS code are only appended when a region is provided.
+///
+/// ```
+/// use bnalambdas::{build_analyzer_command, AnalysisParameters};
+///
+/// let params = AnalysisParameters::simple("france".to_string(), "paris".to_string());
+/// let command = build_analyzer_command(&params, "bucket", "france/paris/24.05.3");
+/// assert_eq!(command[command.len() - 2..], ["france", "paris"]);
+/// ```
+pub fn build_analyzer_command(
+    params: &AnalysisParameters,
+    s3_bucket: &str,
+    s3_dir: &str,
+) -> Vec<String> {
+    let mut command: Vec<String> = vec![
+        "-vv".to_string(),
+        "run".to_string(),
+        "--with-export".to_string(),
+        "s3_custom".to_string(),
+        "--s3-bucket".to_string(),
+        s3_bucket.to_string(),
+        "--s3-dir".to_string(),
+        s3_dir.to_string(),
+        params.country.clone(),
+        params.city.clone(),
+    ];
+    if let Some(region) = &params.region {
+        command.push(region.clone());
+        if let Some(fips_code) = &params.fips_code {
+            command.push(fips_code.clone());
+        }
+    }
+    command
+}
+
+/// Place names normalized the way the BNA API stores them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedPlace {
+    pub country: String,
+    pub region: String,
+    pub city: String,
+}
+
+/// Normalize the names of a place to title case.
+///
+/// The same normalization must be used to look up a city and to create it,
+/// otherwise the lookup misses the cities created previously. Accented
+/// characters are preserved, and the normalized names yield the same slug as
+/// the original ones.
+///
+/// ```
+/// use bnalambdas::normalize_place;
+///
+/// let place = normalize_place("usa", "new mexico", "santa rosa");
+/// assert_eq!(place.country, "Usa");
+/// assert_eq!(place.region, "New Mexico");
+/// assert_eq!(place.city, "Santa Rosa");
+/// ```
+pub fn normalize_place(country: &str, region: &str, city: &str) -> NormalizedPlace {
+    NormalizedPlace {
+        country: country.to_title_case(),
+        region: region.to_title_case(),
+        city: city.to_title_case(),
+    }
+}
+
+/// Create the span describing an analysis.
+///
+/// The span carries the context shared by the lambdas as structured fields,
+/// which are attached to every event logged while the span is entered. The
+/// `branch_id` and `task_arn` fields are empty until they get recorded with
+/// [`Span::record`].
+///
+/// ```
+/// use bnalambdas::{analysis_span, AnalysisParameters};
+/// use uuid::Uuid;
+///
+/// let params = AnalysisParameters::simple("france".to_string(), "paris".to_string());
+/// let span = analysis_span(Uuid::new_v4(), &params);
+/// span.record("branch_id", "br-odd-dream-88611736");
+/// ```
+pub fn analysis_span(state_machine_id: Uuid, params: &AnalysisParameters) -> Span {
+    info_span!(
+        "analysis",
+        %state_machine_id,
+        country = params.country,
+        city = params.city,
+        region = params.region,
+        branch_id = Empty,
+        task_arn = Empty,
+    )
+}
+
+/// Compute the cost of a Fargate task, in USD.
+///
+/// The task size is expressed like in the task definition, with `task_cpu` in
+/// CPU units (1024 units per vCPU) and `task_mem_mb` in MB. Fargate bills by
+/// the second, and a stop time earlier than the start time is considered as
+/// no time elapsed.
+///
+/// ```
+/// use bnalambdas::fargate_cost;
+/// use rust_decimal_macros::dec;
+/// use time::macros::datetime;
+///
+/// // One hour, with 1 vCPU and 2 GB of memory.
+/// let cost = fargate_cost(
+///     datetime!(2024-04-11 03:00:00 UTC),
+///     datetime!(2024-04-11 04:00:00 UTC),
+///     1024,
+///     2048,
+/// );
+/// assert_eq!(cost, dec!(0.04937));
+/// ```
+pub fn fargate_cost(
+    start: OffsetDateTime,
+    stop: OffsetDateTime,
+    task_cpu: u32,
+    task_mem_mb: u32,
+) -> Decimal {
+    let elapsed_secs = (stop - start).whole_seconds().max(0);
+    let hourly_price = (Decimal::from(task_cpu) * FARGATE_VCPU_HOUR_PRICE
+        + Decimal::from(task_mem_mb) * FARGATE_GB_HOUR_PRICE)
+        / dec!(1024);
+    (hourly_price * Decimal::from(elapsed_secs) / dec!(3600)).normalize()
+}
+
+/// Return the HTTP client shared by the lambdas.
+///
+/// The client is created on first use and then reused, along with its
+/// connection pool, for the lifetime of the process.
+pub fn http_client() -> &'static Client {
+    static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
+    HTTP_CLIENT.get_or_init(|| {
+        let timeouts = HttpTimeouts::default();
+        Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.total)
+            .build()
+            .expect("the HTTP client must be valid")
+    })
+}
+
+/// Authenticate the service account.
+///
+/// The token is cached for the lifetime of the process, and reused by the
+/// subsequent invocations of a warm lambda until it gets within
+/// [`TOKEN_EXPIRY_SKEW`] of its expiration.
 pub async fn authenticate_service_account() -> Result<AuthResponse, bnacore::Error> {
+    static TOKEN_CACHE: OnceLock<TokenCache> = OnceLock::new();
     let credentials = get_service_account_credentials().await?;
-    authenticate(&credentials).await
+    TOKEN_CACHE
+        .get_or_init(|| TokenCache::new(TOKEN_EXPIRY_SKEW))
+        .get_or_refresh(&credentials.client_id, || authenticate(&credentials))
+        .await
+}
+
+/// Cache authentication tokens by client id.
+pub struct TokenCache {
+    tokens: Mutex<HashMap<String, (AuthResponse, Instant)>>,
+    skew: Duration,
+    clock: Box<dyn Fn() -> Instant + Send + Sync>,
+}
+
+impl TokenCache {
+    /// Create a new cache refreshing the tokens `skew` before they expire.
+    pub fn new(skew: Duration) -> Self {
+        Self::with_clock(skew, Instant::now)
+    }
+
+    /// Create a new cache using a custom clock.
+    pub fn with_clock<C>(skew: Duration, clock: C) -> Self
+    where
+        C: Fn() -> Instant + Send + Sync + 'static,
+    {
+        Self {
+            tokens: Mutex::new(HashMap::new()),
+            skew,
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Return the cached token of a client, or call `refresh` to obtain a new
+    /// one if there is none or if it is about to expire.
+    pub async fn get_or_refresh<F, Fut>(
+        &self,
+        client_id: &str,
+        refresh: F,
+    ) -> Result<AuthResponse, bnacore::Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<AuthResponse, bnacore::Error>>,
+    {
+        let mut tokens = self.tokens.lock().await;
+        let now = (self.clock)();
+        if let Some((auth, expires_at)) = tokens.get(client_id) {
+            if now + self.skew < *expires_at {
+                return Ok(auth.clone());
+            }
+        }
+        let auth = refresh().await?;
+        let expires_at = now + Duration::from_secs(auth.expires_in.into());
+        tokens.insert(client_id.to_string(), (auth.clone(), expires_at));
+        Ok(auth)
+    }
 }
 
 /// Define a state machine context object.
@@ -211,6 +698,11 @@ pub struct Execution {
 impl Execution {
     /// Parse the execution name into the state machine ID and the scheduled trigger id if available.
     ///
+    /// The name is expected to have the `<state_machine_id>[_<scheduled_trigger_id>]`
+    /// format. Only the first two `_`-separated segments are meaningful, any other
+    /// segment is ignored. An empty name is not a valid state machine ID and
+    /// returns an error.
+    ///
     /// ```
     /// use bnalambdas::Execution;
     /// use uuid::Uuid;
@@ -232,8 +724,8 @@ impl Execution {
     /// );
     /// ```
     pub fn ids(&self) -> Result<(Uuid, Option<Uuid>), uuid::Error> {
-        let mut parts = self.name.split('_');
-        let state_machine_id = parts.next().unwrap().parse::<Uuid>()?;
+        let mut parts = self.name.trim().split('_');
+        let state_machine_id = parts.next().unwrap_or_default().parse::<Uuid>()?;
         let scheduled_trigger_id = parts.next().map(|s| s.parse::<Uuid>()).transpose()?;
 
         Ok((state_machine_id, scheduled_trigger_id))
@@ -254,17 +746,85 @@ pub struct StateMachine {
     pub name: String,
 }
 
-pub fn update_pipeline(
+/// Describe the outcome of a pipeline update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineUpdate {
+    /// Status code of the successful response.
+    pub status: StatusCode,
+    /// Whether the update had to be retried.
+    pub retried: bool,
+}
+
+/// Update a pipeline from an async handler.
+///
+/// The update runs on the blocking thread pool, so that neither the blocking
+/// requests nor the delays between the retries stall the async runtime. See
+/// [`update_pipeline_blocking`] for the retry policy.
+pub async fn update_pipeline(
+    client: &'static Client,
+    url: &str,
+    auth: &AuthResponse,
+    pipeline: &BNAPipeline,
+) -> Result<PipelineUpdate, lambda_runtime::Error> {
+    let url = url.to_string();
+    let auth = auth.clone();
+    let pipeline = pipeline.clone();
+    run_blocking(move || update_pipeline_blocking(client, &url, &auth, &pipeline)).await
+}
+
+/// Run a request of the blocking HTTP client from an async handler.
+///
+/// The request runs on the blocking thread pool, so that it does not stall the
+/// async runtime.
+pub async fn run_blocking<T, F>(request: F) -> Result<T, lambda_runtime::Error>
+where
+    F: FnOnce() -> Result<T, reqwest::Error> + Send + 'static,
+    T: Send + 'static,
+{
+    Ok(tokio::task::spawn_blocking(request).await??)
+}
+
+/// Update a pipeline.
+///
+/// Pipeline patches are idempotent, therefore connection errors, timeouts and
+/// 5xx responses are retried up to 3 times, with an exponential backoff capped
+/// at [`bnacore::MAX_BACKOFF`]. Any other error is returned immediately.
+pub fn update_pipeline_blocking(
+    client: &Client,
     url: &str,
     auth: &AuthResponse,
     pipeline: &BNAPipeline,
-) -> Result<reqwest::blocking::Response, reqwest::Error> {
-    Client::new()
-        .patch(url)
-        .bearer_auth(auth.access_token.clone())
-        .json(&pipeline)
-        .send()?
-        .error_for_status()
+) -> Result<PipelineUpdate, reqwest::Error> {
+    let mut attempt: u32 = 0;
+    loop {
+        let res = client
+            .patch(url)
+            .bearer_auth(auth.access_token.clone())
+            .json(&pipeline)
+            .send()
+            .and_then(|res| res.error_for_status());
+        match res {
+            Ok(res) => {
+                return Ok(PipelineUpdate {
+                    status: res.status(),
+                    retried: attempt > 0,
+                })
+            }
+            Err(err) if attempt < UPDATE_PIPELINE_MAX_RETRIES && is_transient(&err) => {
+                std::thread::sleep(backoff_delay(UPDATE_PIPELINE_BASE_DELAY, attempt));
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Return true if the error is likely to be transient and the request can be
+/// retried.
+fn is_transient(err: &reqwest::Error) -> bool {
+    err.is_connect()
+        || err.is_timeout()
+        || err.status().is_some_and(|status| status.is_server_error())
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -273,13 +833,40 @@ pub struct AWSS3 {
 }
 
 impl AWSS3 {
-    /// Returns the get version of this [`AWSS3`].
-    pub fn get_version(&self) -> String {
-        self.destination
+    /// Returns the version of this [`AWSS3`].
+    pub fn get_version(&self) -> Result<String, bnacore::Error> {
+        let (_, _, _, version) = self.parts()?;
+        Ok(version.to_ubuntu())
+    }
+
+    /// Returns the country, region, city and version of this [`AWSS3`].
+    ///
+    /// The destination is expected to end with
+    /// `<country>/<region>/<city>/<version>`. Any leading prefix, like the
+    /// `results/` folder of the analysis bucket, is ignored.
+    pub fn parts(&self) -> Result<(String, String, String, Calver), bnacore::Error> {
+        let parts = self
+            .destination
             .split_terminator('/')
-            .last()
-            .expect("the destination field must contain a `/` symbol")
-            .to_owned()
+            .collect::<Vec<&str>>();
+        let [.., country, region, city, version] = parts.as_slice() else {
+            return Err(bnacore::Error::InvalidArgument(format!(
+                "the destination must end with `<country>/<region>/<city>/<version>`, got `{}`",
+                self.destination
+            )));
+        };
+        let version = Calver::try_from_ubuntu(version).map_err(|e| {
+            bnacore::Error::InvalidArgument(format!(
+                "invalid version in destination `{}`: {e}",
+                self.destination
+            ))
+        })?;
+        Ok((
+            country.to_string(),
+            region.to_string(),
+            city.to_string(),
+            version,
+        ))
     }
 }
 
@@ -293,6 +880,9 @@ pub struct Fargate {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+    use tracing::info;
+    use tracing_test::traced_test;
 
     // Uses the example provided in the official Step Function documentation:
     // https://docs.aws.amazon.com/step-functions/latest/dg/input-output-contextobject.html#contextobject-format
@@ -326,6 +916,409 @@ mod tests {
         assert_eq!(deserialized.execution.name, "executionName")
     }
 
+    /// Build a service account secret with a specific secret string.
+    fn service_account_secret(secret_string: &str) -> SecretValue {
+        let raw_json = serde_json::json!({
+            "ARN": "arn:aws:secretsmanager:us-west-2:123456789012:secret:BROKENSPOKE_ANALYZER_SERVICE_ACCOUNT_CREDENTIALS-W9OPPc",
+            "Name": "BROKENSPOKE_ANALYZER_SERVICE_ACCOUNT_CREDENTIALS",
+            "VersionId": "2da56f31-38b6-4ea3-92b0-b15d1189f4d2",
+            "SecretString": secret_string,
+            "VersionStages": ["AWSCURRENT"],
+            "CreatedDate": "2023-12-28T16:37:14.751000-06:00"
+        });
+        serde_json::from_value::<SecretValue>(raw_json).unwrap()
+    }
+
+    #[test]
+    fn test_app_client_credentials_from_secret() {
+        let secret =
+            service_account_secret(r#"{"client_id":"my-client","client_secret":"my-secret"}"#);
+        let credentials = AppClientCredentials::from_secret(&secret).unwrap();
+        assert_eq!(credentials.client_id, "my-client");
+        assert_eq!(credentials.client_secret, "my-secret");
+    }
+
+    #[test]
+    fn test_app_client_credentials_missing_client_secret() {
+        let secret = service_account_secret(r#"{"client_id":"my-client"}"#);
+        let err = AppClientCredentials::from_secret(&secret).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "no value matching the key `client_secret` in secret `BROKENSPOKE_ANALYZER_SERVICE_ACCOUNT_CREDENTIALS`"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_cache() {
+        use std::sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        };
+
+        // Use a clock which can be moved forward manually.
+        let start = Instant::now();
+        let elapsed = Arc::new(std::sync::Mutex::new(Duration::ZERO));
+        let clock_elapsed = elapsed.clone();
+        let cache = TokenCache::with_clock(Duration::from_secs(60), move || {
+            start + *clock_elapsed.lock().unwrap()
+        });
+
+        let refreshes = AtomicU32::new(0);
+        let refresh = || async {
+            let count = refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(AuthResponse {
+                access_token: format!("token-{count}"),
+                expires_in: 3600,
+                token_type: "Bearer".to_string(),
+            })
+        };
+
+        // The second call within the TTL reuses the token.
+        let auth = cache.get_or_refresh("client", refresh).await.unwrap();
+        assert_eq!(auth.access_token, "token-1");
+        *elapsed.lock().unwrap() = Duration::from_secs(3000);
+        let auth = cache.get_or_refresh("client", refresh).await.unwrap();
+        assert_eq!(auth.access_token, "token-1");
+
+        // A token within the skew of its expiration is refreshed.
+        *elapsed.lock().unwrap() = Duration::from_secs(3550);
+        let auth = cache.get_or_refresh("client", refresh).await.unwrap();
+        assert_eq!(auth.access_token, "token-2");
+
+        // Tokens are cached per client.
+        let auth = cache.get_or_refresh("other", refresh).await.unwrap();
+        assert_eq!(auth.access_token, "token-3");
+        assert_eq!(refreshes.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_update_pipeline_retry() {
+        use wiremock::{
+            matchers::{header, method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/ratings/analyses/1"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/ratings/analyses/1"))
+            .and(header("Authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/ratings/analyses/1", server.uri());
+        let outcome = tokio::task::spawn_blocking(move || {
+            let auth = AuthResponse {
+                access_token: "token".to_string(),
+                expires_in: 3600,
+                token_type: "Bearer".to_string(),
+            };
+            update_pipeline_blocking(&Client::new(), &url, &auth, &BNAPipeline::default())
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            outcome,
+            PipelineUpdate {
+                status: StatusCode::OK,
+                retried: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_serde_pipeline_step() {
+        for (step, name) in [
+            (BNAPipelineStep::Analysis, "Analysis"),
+            (BNAPipelineStep::Cleanup, "Cleanup"),
+            (BNAPipelineStep::Setup, "Setup"),
+            (BNAPipelineStep::Save, "Save"),
+        ] {
+            assert_eq!(serde_json::to_value(step).unwrap(), name);
+            assert_eq!(name.parse::<BNAPipelineStep>().unwrap(), step);
+        }
+
+        let pipeline = BNAPipeline {
+            step: Some(BNAPipelineStep::Cleanup),
+            ..Default::default()
+        };
+        let value = serde_json::to_value(&pipeline).unwrap();
+        assert_eq!(value["step"], "Cleanup");
+        let deserialized = serde_json::from_value::<BNAPipeline>(value).unwrap();
+        assert_eq!(deserialized.step, Some(BNAPipelineStep::Cleanup));
+    }
+
+    #[test]
+    fn test_pipeline_step_transitions() {
+        use BNAPipelineStep::*;
+
+        // Valid transitions.
+        for (from, to) in [
+            (Setup, Setup),
+            (Setup, Analysis),
+            (Analysis, Analysis),
+            (Analysis, Save),
+            (Save, Save),
+            (Save, Cleanup),
+            (Cleanup, Cleanup),
+        ] {
+            assert!(from.can_transition_to(&to), "{from:?} -> {to:?}");
+        }
+
+        // Invalid transitions.
+        for (from, to) in [
+            (Setup, Cleanup),
+            (Setup, Save),
+            (Analysis, Setup),
+            (Analysis, Cleanup),
+            (Cleanup, Setup),
+        ] {
+            assert!(!from.can_transition_to(&to), "{from:?} -> {to:?}");
+        }
+
+        assert!(Setup < Analysis && Analysis < Save && Save < Cleanup);
+        assert_eq!(Cleanup.ordinal(), 3);
+    }
+
+    #[test]
+    fn test_analysis_parameters_validate() {
+        let params = |fips_code: Option<&str>| {
+            AnalysisParameters::new(
+                "usa".to_string(),
+                "santa rosa".to_string(),
+                Some("new mexico".to_string()),
+                fips_code.map(String::from),
+            )
+        };
+
+        // Valid FIPS codes.
+        for fips_code in [None, Some("0"), Some("35019"), Some("3570670")] {
+            assert!(params(fips_code).validate().is_ok(), "{fips_code:?}");
+        }
+
+        // Invalid FIPS codes.
+        for fips_code in ["", "00", "357067", "35706700", "35a70", "35 70670"] {
+            assert!(params(Some(fips_code)).validate().is_err(), "{fips_code}");
+        }
+
+        // Missing country or city.
+        assert!(
+            AnalysisParameters::simple("".to_string(), "austin".to_string())
+                .validate()
+                .is_err()
+        );
+        assert!(
+            AnalysisParameters::simple("usa".to_string(), " ".to_string())
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_normalize_place() {
+        let place = normalize_place("usa", "new mexico", "santa rosa");
+        assert_eq!(
+            place,
+            NormalizedPlace {
+                country: "Usa".to_string(),
+                region: "New Mexico".to_string(),
+                city: "Santa Rosa".to_string(),
+            }
+        );
+
+        // Already normalized names are left untouched.
+        assert_eq!(normalize_place("Usa", "New Mexico", "Santa Rosa"), place);
+
+        // Accented characters are preserved.
+        let place = normalize_place("brasil", "são paulo", "são josé dos campos");
+        assert_eq!(place.region, "São Paulo");
+        assert_eq!(place.city, "São José Dos Campos");
+        let place = normalize_place("france", "île-de-france", "ÉVRY");
+        assert_eq!(place.region, "Île De France");
+        assert_eq!(place.city, "Évry");
+
+        // The normalized place yields the same slug as the original one.
+        let params = AnalysisParameters::with_region(
+            "france".to_string(),
+            "évry".to_string(),
+            "île-de-france".to_string(),
+        );
+        let normalized = AnalysisParameters::with_region(place.country, place.city, place.region);
+        assert_eq!(params.slug(), normalized.slug());
+    }
+
+    #[test]
+    fn test_fargate_cost() {
+        use time::macros::datetime;
+
+        // 10 minutes with 0.25 vCPU and 0.5 GB of memory.
+        let start = datetime!(2024-04-11 03:00:00 UTC);
+        let stop = datetime!(2024-04-11 03:10:00 UTC);
+        let cost = fargate_cost(start, stop, 256, 512);
+        assert_eq!(cost.round_dp(8), dec!(0.00205708));
+
+        // Stopped before it started.
+        assert_eq!(fargate_cost(stop, start, 256, 512), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_pipeline_builder() {
+        let state_machine_id = Uuid::parse_str("9ff90cac-0cf5-4923-897f-4416df5e7328").unwrap();
+        let start_time = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let end_time = OffsetDateTime::from_unix_timestamp(1_700_003_600).unwrap();
+
+        let built = BNAPipeline::update(state_machine_id)
+            .cost(Decimal::new(822, 2))
+            .end_time(end_time)
+            .start_time(start_time)
+            .step(BNAPipelineStep::Save)
+            .build();
+        let hand_built = BNAPipeline {
+            cost: Some(Decimal::new(822, 2)),
+            end_time: Some(end_time),
+            start_time: Some(start_time),
+            state_machine_id,
+            step: Some(BNAPipelineStep::Save),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&built).unwrap(),
+            serde_json::to_value(&hand_built).unwrap()
+        );
+
+        // The fields which are not set are not sent.
+        let built = BNAPipeline::update(state_machine_id)
+            .torn_down(true)
+            .build();
+        assert_eq!(
+            serde_json::to_value(&built).unwrap(),
+            serde_json::json!({
+                "state_machine_id": "9ff90cac-0cf5-4923-897f-4416df5e7328",
+                "torn_down": true,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("france", "france", "paris")]
+    #[case("belgium", "flanders", "antwerp")]
+    #[case("usa", "texas", "austin")]
+    fn test_analysis_parameters_slug_round_trip(
+        #[case] country: &str,
+        #[case] region: &str,
+        #[case] city: &str,
+    ) {
+        let params = AnalysisParameters::with_region(
+            country.to_string(),
+            city.to_string(),
+            region.to_string(),
+        );
+        let id = Uuid::parse_str("9ff90cac-0cf5-4923-897f-4416df5e7328").unwrap();
+        for slug in [params.slug(), params.slug_with_id(id)] {
+            let parsed = AnalysisParameters::from_slug(&slug).unwrap();
+            assert_eq!(parsed.country, country);
+            assert_eq!(parsed.region.as_deref(), Some(region));
+            assert_eq!(parsed.city, city);
+            assert_eq!(parsed.slug(), params.slug());
+        }
+    }
+
+    #[rstest]
+    #[case::multi_word_names("usa-new-mexico-santa-rosa")]
+    #[case::too_short("france-paris")]
+    #[case::empty_part("france--paris")]
+    #[case::empty("")]
+    fn test_analysis_parameters_from_slug_ambiguous(#[case] slug: &str) {
+        assert!(AnalysisParameters::from_slug(slug).is_none());
+    }
+
+    #[test]
+    fn test_analysis_parameters_slug() {
+        let params = AnalysisParameters::with_region(
+            "United States".to_string(),
+            "Santa Rosa".to_string(),
+            "New Mexico".to_string(),
+        );
+        assert_eq!(params.slug(), "united-states-new-mexico-santa-rosa");
+
+        // Without a region, the country is used instead.
+        let params = AnalysisParameters::simple("France".to_string(), "Paris".to_string());
+        assert_eq!(params.slug(), "france-france-paris");
+
+        // The slug is truncated to its maximum length.
+        let city = "a".repeat(SLUG_LENGTH - "fr-idf-".len());
+        let params =
+            AnalysisParameters::with_region("fr".to_string(), city.clone(), "idf".to_string());
+        assert_eq!(params.slug(), format!("fr-idf-{city}"));
+        assert_eq!(params.slug().len(), SLUG_LENGTH);
+        let params = AnalysisParameters::with_region(
+            "fr".to_string(),
+            format!("{city}b"),
+            "idf".to_string(),
+        );
+        assert_eq!(params.slug(), format!("fr-idf-{city}"));
+    }
+
+    #[test]
+    fn test_build_analyzer_command() {
+        let prefix = [
+            "-vv",
+            "run",
+            "--with-export",
+            "s3_custom",
+            "--s3-bucket",
+            "brokenspoke-analyzer",
+            "--s3-dir",
+        ];
+        let cases = [
+            (
+                AnalysisParameters::simple("france".to_string(), "paris".to_string()),
+                "france/paris/24.05.3",
+                vec!["france", "paris"],
+            ),
+            (
+                AnalysisParameters::with_region(
+                    "usa".to_string(),
+                    "santa rosa".to_string(),
+                    "new mexico".to_string(),
+                ),
+                "usa/new mexico/santa rosa/24.05.3",
+                vec!["usa", "santa rosa", "new mexico", "0"],
+            ),
+            (
+                AnalysisParameters::with_fips_code(
+                    "usa".to_string(),
+                    "santa rosa".to_string(),
+                    "new mexico".to_string(),
+                    "3570670".to_string(),
+                ),
+                "usa/new mexico/santa rosa/24.05.3",
+                vec!["usa", "santa rosa", "new mexico", "3570670"],
+            ),
+        ];
+        for (params, s3_dir, location) in cases {
+            let command = build_analyzer_command(&params, "brokenspoke-analyzer", s3_dir);
+            let mut expected = prefix.to_vec();
+            expected.push(s3_dir);
+            expected.extend(location);
+            assert_eq!(command, expected);
+        }
+    }
+
+    #[test]
+    fn test_http_client_is_shared() {
+        assert!(std::ptr::eq(http_client(), http_client()));
+    }
+
     #[test]
     fn test_ids_partial() {
         let name = "e6aade5a-b343-120b-dbaa-bd916cd99221".to_string();
@@ -339,4 +1332,85 @@ mod tests {
         assert_eq!(state_machine_id, Uuid::parse_str(&name).unwrap());
         assert_eq!(schedule_trigger_id, None);
     }
+
+    #[rstest]
+    #[case("usa/new mexico/santa rosa/24.05.3")]
+    #[case("results/usa/new mexico/santa rosa/24.05.3")]
+    #[case("analyses/results/usa/new mexico/santa rosa/24.05.3/")]
+    fn test_awss3_parts(#[case] destination: &str) {
+        let aws_s3 = AWSS3 {
+            destination: destination.to_string(),
+        };
+        let (country, region, city, version) = aws_s3.parts().unwrap();
+        assert_eq!(country, "usa");
+        assert_eq!(region, "new mexico");
+        assert_eq!(city, "santa rosa");
+        assert_eq!(version, Calver::try_from_ubuntu("24.05.3").unwrap());
+        assert_eq!(aws_s3.get_version().unwrap(), "24.05.3");
+    }
+
+    #[test]
+    fn test_awss3_parts_without_version() {
+        let aws_s3 = AWSS3 {
+            destination: "usa/new mexico/santa rosa".to_string(),
+        };
+        assert!(aws_s3.parts().is_err());
+        assert!(aws_s3.get_version().is_err());
+    }
+
+    fn execution(name: &str) -> Execution {
+        Execution {
+            id: "id".to_string(),
+            name: name.to_string(),
+            role_arn: "role".to_string(),
+            start_time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn test_execution_ids_empty_name() {
+        assert!(execution("").ids().is_err());
+        assert!(execution("  ").ids().is_err());
+    }
+
+    #[test]
+    fn test_execution_ids_three_segments() {
+        let (state_machine_id, scheduled_trigger_id) = execution(
+            "e6aade5a-b343-120b-dbaa-bd916cd99221_04ca18b9-6e0c-1aa5-2c3f-d4b445f840bc_extra",
+        )
+        .ids()
+        .unwrap();
+        assert_eq!(
+            state_machine_id,
+            Uuid::parse_str("e6aade5a-b343-120b-dbaa-bd916cd99221").unwrap()
+        );
+        assert_eq!(
+            scheduled_trigger_id,
+            Some(Uuid::parse_str("04ca18b9-6e0c-1aa5-2c3f-d4b445f840bc").unwrap())
+        );
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_analysis_span() {
+        let params = AnalysisParameters::with_region(
+            "usa".to_string(),
+            "santa rosa".to_string(),
+            "new mexico".to_string(),
+        );
+        let state_machine_id = Uuid::parse_str("9ff90cac-0cf5-4923-897f-4416df5e7328").unwrap();
+        let span = analysis_span(state_machine_id, &params);
+        span.record("branch_id", "br-odd-dream-88611736");
+        span.in_scope(|| info!(active_branches = 3, "creating branch"));
+
+        assert!(logs_contain(
+            "state_machine_id=9ff90cac-0cf5-4923-897f-4416df5e7328"
+        ));
+        assert!(logs_contain(r#"country="usa""#));
+        assert!(logs_contain(r#"city="santa rosa""#));
+        assert!(logs_contain(r#"region="new mexico""#));
+        assert!(logs_contain(r#"branch_id="br-odd-dream-88611736""#));
+        assert!(logs_contain("active_branches=3"));
+        assert!(!logs_contain("task_arn="));
+    }
 }
@@ -1,7 +1,7 @@
 use aws_config::BehaviorVersion;
 use aws_lambda_events::event::sqs::SqsEvent;
 use aws_sdk_s3::primitives::ByteStream;
-use bnacore::combine::combine_mem;
+use bnacore::combine::append_mem;
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use minijinja::Environment;
 use serde_json::Value;
@@ -46,19 +46,13 @@ async fn function_handler(event: LambdaEvent<SqsEvent>) -> Result<(), Error> {
 
     // Convert it to pdf.
     let pdf_page_1 = pdf_convert(&rendered, &fontdb)?;
-    std::fs::write("page_1.pdf", &pdf_page_1)?;
 
     // Load the second page and convert it to pdf.
     let source_page_2 = include_str!("../../assets/visuals/template-scorecard-pg2-v23.1.svg");
     let pdf_page_2 = pdf_convert(source_page_2, &fontdb)?;
-    std::fs::write("page_2.pdf", &pdf_page_2)?;
 
     // Combine the 2 pages.
-    let full_pdf = vec![pdf_page_1.as_slice(), pdf_page_2.as_slice()];
-    let mut scorecard = combine_mem(full_pdf.as_slice())?;
-    let mut buffer: Vec<u8> = Vec::new();
-    scorecard.save_to(&mut buffer)?;
-    scorecard.save("scorecard_test.pdf")?;
+    let buffer = append_mem(&pdf_page_1, &pdf_page_2)?;
 
     // Upload to S3.
     let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
@@ -1,196 +1,316 @@
-use bnacore::build_cmd_args;
+use bnacore::{
+    bundle::{BundleReport, FileType, GroupBy},
+    combine::batch_append,
+    scorecard::{shortscorecard::ShortScoreCard, Format},
+    template::{render, Exporter, RenderOptions},
+};
+use clap::{Parser, ValueEnum, ValueHint};
 use color_eyre::{
     eyre::{eyre, Report},
     Result,
 };
+use spokes::bundle::{run, BundleOptions};
 use std::{
-    ffi::OsStr,
     fs,
-    path::PathBuf,
-    process::{Command, Output},
+    path::{Path, PathBuf},
 };
-use tracing::{debug, info};
-use walkdir::WalkDir;
+use tracing::info;
+
+/// Describe the inputs and the outputs of the pipeline.
+struct Pipeline {
+    /// Format of the City Ratings file.
+    format: Format,
+    /// City Ratings file.
+    city_ratings: PathBuf,
+    /// SVG template of the brochures.
+    brochure_template: PathBuf,
+    /// PDF document appended to every brochure.
+    information_page: PathBuf,
+    /// Directory where the brochures and the bundles are generated.
+    output_dir: PathBuf,
+    /// Exporter converting the brochures to PDF.
+    exporter: Exporter,
+}
+
+/// Generate the BNA brochures.
+///
+/// Relative default paths are resolved from the root of the repository, making
+/// the pipeline independent of the current working directory.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    /// City Ratings file [default: assets/city-ratings/latest.csv]
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    city_ratings: Option<PathBuf>,
+    /// Format of the City Ratings file
+    #[clap(long, value_enum, default_value_t = CliFormat::V24)]
+    format: CliFormat,
+    /// Version of the brochure template
+    #[clap(long, default_value = "v23.2")]
+    template_version: String,
+    /// SVG template of the brochures [default: assets/visuals/template-scorecard-pg1-<TEMPLATE_VERSION>.svg]
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    brochure_template: Option<PathBuf>,
+    /// Information page appended to every brochure [default: assets/visuals/template-scorecard-pg2-v23.1.pdf]
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    information_page: Option<PathBuf>,
+    /// Output directory [default: pipelines/brochures/output]
+    #[clap(long, value_hint = ValueHint::DirPath)]
+    output_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CliFormat {
+    V21,
+    V23,
+    V24,
+}
+
+impl From<CliFormat> for Format {
+    fn from(value: CliFormat) -> Self {
+        match value {
+            CliFormat::V21 => Format::V21,
+            CliFormat::V23 => Format::V23,
+            CliFormat::V24 => Format::V24,
+        }
+    }
+}
+
+impl Cli {
+    /// Build the pipeline, filling the missing paths with their defaults.
+    fn pipeline(&self, exporter: Exporter) -> Pipeline {
+        let top_dir = top_dir();
+        let asset_dir = top_dir.join("assets");
+        Pipeline {
+            format: self.format.into(),
+            city_ratings: self
+                .city_ratings
+                .clone()
+                .unwrap_or_else(|| asset_dir.join("city-ratings/latest.csv")),
+            brochure_template: self.brochure_template.clone().unwrap_or_else(|| {
+                asset_dir.join(format!(
+                    "visuals/template-scorecard-pg1-{}.svg",
+                    self.template_version
+                ))
+            }),
+            information_page: self
+                .information_page
+                .clone()
+                .unwrap_or_else(|| asset_dir.join("visuals/template-scorecard-pg2-v23.1.pdf")),
+            output_dir: self
+                .output_dir
+                .clone()
+                .unwrap_or_else(|| top_dir.join("pipelines/brochures/output")),
+            exporter,
+        }
+    }
+}
+
+/// Return the root directory of the repository.
+fn top_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .ancestors()
+        .nth(2)
+        .expect("the pipeline must be located 2 levels below the root of the repository")
+        .to_path_buf()
+}
 
 fn main() -> Result<(), Report> {
     // Setup the application.
     color_eyre::install()?;
+    let cli = Cli::parse();
 
     // Setup logging.
     tracing_subscriber::fmt::fmt()
         .with_max_level(tracing::Level::DEBUG)
         .init();
 
-    // Parameters
-    let format = "v24";
-    let _city_rating_version = "v23.2";
-
     // Get the paths.
-    let top_dir = PathBuf::from("../../").canonicalize()?;
-    let asset_dir = top_dir.join("assets");
-    let output_dir = top_dir.join("pipelines/brochures/output");
-    let brochure_template = asset_dir
-        .join("visuals/template-scorecard-pg1-v23.2.svg")
-        .canonicalize()?;
-    let brochure_information_page = asset_dir.join("visuals/template-scorecard-pg2-v23.1.pdf");
-    let city_ratings = asset_dir.join("city-ratings/latest.csv").canonicalize()?;
+    let pipeline = cli.pipeline(Exporter::Inkscape);
+    run_pipeline(&pipeline)?;
+
+    info!("✅ Done");
+    Ok(())
+}
+
+/// Generate the brochures and bundle them.
+///
+/// The exporter is the only external process which may be spawned.
+fn run_pipeline(pipeline: &Pipeline) -> Result<BundleReport, Report> {
+    let output_dir = &pipeline.output_dir;
     let brochure_template_copy = output_dir.join("scorecard.svg");
     let shortcodes = output_dir.join("scorecard.csv");
 
     // Create the output directory.
     info!("📁 Creating the output directory...");
-    fs::create_dir_all(&output_dir)?;
+    fs::create_dir_all(output_dir)?;
 
     // Copy the brochure template from the asset directory.
     info!("⚙️  Copying the brochure template...");
-    fs::copy(brochure_template, &brochure_template_copy)?;
+    fs::copy(&pipeline.brochure_template, &brochure_template_copy)?;
 
     // Convert the City Ratings file to a Shortcode file.
     info!("🔄 Converting the City Ratings file to a Shortcode file...");
-    let output = Command::new("cargo")
-        .arg("run")
-        .arg("-p")
-        .arg("spokes")
-        .arg("--bin")
-        .arg("shortcodes")
-        .arg(format)
-        .arg(&city_ratings)
-        .arg(&shortcodes)
-        .output()?;
-    process_output(&output)?;
-
-    //  Generate SVG files.
-    info!("📄 Generating SVG files...");
-    let output = Command::new("cargo")
-        .arg("run")
-        .arg("-p")
-        .arg("spokes")
-        .arg("--bin")
-        .arg("svggloo")
-        .arg("--")
-        .arg("--field")
-        .arg("co")
-        .arg("--field")
-        .arg("st")
-        .arg("--field")
-        .arg("ci")
-        .arg("--exporter")
-        .arg("inkscape")
-        .arg(&brochure_template_copy)
-        .arg(&output_dir)
-        .output()?;
-    process_output(&output)?;
-
-    // Collect all the SVGs.
-    debug!("🗄️  Collecting the generated SVG files...");
-    let mut svg_files = Vec::new();
-    for entry in WalkDir::new(&output_dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.into_path();
-
-        // Ignore the template itself.
-        if path.file_name() == brochure_template_copy.file_name() {
-            continue;
-        }
+    let short_scorecards =
+        ShortScoreCard::from_city_ratings(&pipeline.format, &pipeline.city_ratings)?;
+    ShortScoreCard::write_csv(&shortcodes, &short_scorecards)?;
 
-        // Otherwise ensure the file is a .svg and add it to the list.
-        if let Some(ext) = path.extension() {
-            if ext == OsStr::new("svg") {
-                let filename = path.file_name().unwrap();
-                let filename_str = filename.to_str().unwrap();
-                svg_files.push(filename_str.to_string())
-            }
+    //  Generate the SVG and PDF files.
+    info!("📄 Generating SVG and PDF files...");
+    let options = RenderOptions {
+        exporter: Some(pipeline.exporter),
+        field_based_name: Some(vec!["co".to_string(), "st".to_string(), "ci".to_string()]),
+        separator: Some("-".to_string()),
+        ..RenderOptions::default()
+    };
+    let report = render(&brochure_template_copy, output_dir, &options)?;
+    info!("{report}");
+    if !report.is_success() {
+        for (index, err) in &report.failures {
+            info!("record {index}: {err}");
         }
+        return Err(eyre!("{report}"));
     }
 
-    // // Generate the PDF files.
-    // info!("📃 Generating PDF files...");
-    // // generate_pdf(&svg_files, &output_dir)?;
-    // let cmd_args_groups = build_cmd_args(
-    //     "inkscape",
-    //     &[
-    //         "--export-area-drawing".to_string(),
-    //         "--batch-process".to_string(),
-    //         "--export-type=pdf".to_string(),
-    //     ],
-    //     &svg_files,
-    //     bnacore::MAX_PROMPT_LENGTH,
-    // )?;
-    // for cmd_args in cmd_args_groups {
-    //     let mut cmd = Command::new("inkscape");
-    //     cmd.args(cmd_args).current_dir(&output_dir);
-    //     let output = cmd.output().map_err(Report::new)?;
-    //     process_output_with_command(&output, &cmd)?
-    // }
-
     // Append information page.
     info!("📎 Append information page");
-    let pdf_files = svg_files
+    let pdf_files = report
+        .rendered
         .iter()
-        .map(|f| output_dir.join(f))
         .map(|f| f.with_extension("pdf"))
-        .map(|f| f.to_str().unwrap().to_string())
-        .collect::<Vec<String>>();
-    let cmd_args_groups = build_cmd_args(
-        "cargo",
-        &[
-            "run".to_string(),
-            "-p".to_string(),
-            "spokes".to_string(),
-            "--bin".to_string(),
-            "appender".to_string(),
-            brochure_information_page.to_str().unwrap().to_string(),
-        ],
-        &pdf_files,
-        bnacore::MAX_PROMPT_LENGTH,
+        .collect::<Vec<PathBuf>>();
+    batch_append(
+        &pdf_files
+            .iter()
+            .map(PathBuf::as_path)
+            .collect::<Vec<&Path>>(),
+        &pipeline.information_page,
     )?;
-    for cmd_args in cmd_args_groups {
-        let mut cmd = Command::new("cargo");
-        cmd.args(cmd_args).current_dir(&output_dir);
-        let output = cmd.output().map_err(Report::new)?;
-        process_output_with_command(&output, &cmd)?
-    }
 
     // Bundle the brochures.
     info!("📦 Bundling the brochures...");
-    let output = Command::new("cargo")
-        .arg("run")
-        .arg("-p")
-        .arg("spokes")
-        .arg("--bin")
-        .arg("bundler")
-        .arg("--")
-        .arg("pdf")
-        .arg("country")
-        .arg(&output_dir.canonicalize()?)
-        .output()?;
-    process_output(&output)?;
+    let report = run(BundleOptions {
+        input_dir: output_dir.canonicalize()?,
+        group_by: GroupBy::Country,
+        filetype: FileType::Pdf,
+        strict: false,
+        manifest: false,
+    })?;
+    info!("{report}");
 
-    info!("✅ Done");
-    Ok(())
+    Ok(report)
 }
 
-fn process_output_with_command(output: &Output, cmd: &Command) -> Result<(), Report> {
-    if output.status.success() {
-        return Ok(());
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bnacore::{
+        combine::page_count,
+        scorecard::{scorecard24::ScoreCard24, ScorecardCsv},
+        template::{export_with_svg2pdf, system_fontdb},
+    };
 
-    Err(eyre!(
-        "The command {:?} failed with status code {:?} and the following error: {:?}.\n The following arguments were passed to the command:\n {:?}",
-        cmd.get_program(),
-        output.status.code(),
-        String::from_utf8_lossy(&output.stderr),
-        cmd.get_args().collect::<Vec<&OsStr>>()
-    ))
-}
+    /// The default paths are absolute, therefore they do not depend on the
+    /// working directory.
+    #[test]
+    fn test_default_paths_ignore_working_directory() {
+        let top_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+
+        let pipeline = Cli::parse_from(["brochures"]).pipeline(Exporter::Inkscape);
+        assert!(pipeline.brochure_template.is_absolute());
+        assert!(pipeline.information_page.is_absolute());
+        assert_eq!(
+            pipeline.brochure_template.canonicalize().unwrap(),
+            top_dir
+                .join("assets/visuals/template-scorecard-pg1-v23.2.svg")
+                .canonicalize()
+                .unwrap()
+        );
+        assert_eq!(
+            pipeline.information_page.canonicalize().unwrap(),
+            top_dir
+                .join("assets/visuals/template-scorecard-pg2-v23.1.pdf")
+                .canonicalize()
+                .unwrap()
+        );
+        assert!(pipeline.city_ratings.is_absolute());
+        assert!(pipeline
+            .city_ratings
+            .ends_with("assets/city-ratings/latest.csv"));
+        assert!(pipeline.output_dir.is_absolute());
+        assert!(pipeline.output_dir.ends_with("pipelines/brochures/output"));
 
-fn process_output(output: &Output) -> Result<(), Report> {
-    if output.status.success() {
-        return Ok(());
+        let pipeline = Cli::parse_from(["brochures", "--template-version", "v23.4"])
+            .pipeline(Exporter::Inkscape);
+        assert!(pipeline.brochure_template.exists());
     }
 
-    Err(eyre!(
-        "The command  failed with status code {:?} and the following error: {:?}.",
-        output.status.code(),
-        String::from_utf8_lossy(&output.stderr),
-    ))
+    #[test]
+    fn test_run_pipeline() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Prepare the City Ratings file.
+        let raw_json = r#"
+          {
+            "city": "Christchurch",
+            "state": "CAN",
+            "state_full": "Canterbury",
+            "country": "New Zealand",
+            "region": "New Zealand",
+            "year": 2023,
+            "census_population": 389300,
+            "census_latitude": -43.532,
+            "census_longitude": 172.6306,
+            "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+            "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+            "bna_rounded_score": 53,
+            "creation_date": "2023-04-17T16:31:00Z",
+            "filename": "_Christchurch_v23.1.csv"
+          }"#;
+        let christchurch = serde_json::from_str::<ScoreCard24>(raw_json).unwrap();
+        let mut wellington = christchurch.clone();
+        wellington.city = "Wellington".to_string();
+        wellington.state = Some("WGN".to_string());
+        let city_ratings = dir.path().join("city_ratings.csv");
+        ScoreCard24::to_csv(&city_ratings, &[christchurch, wellington]).unwrap();
+
+        // Prepare the brochure template.
+        let brochure_template = dir.path().join("template.svg");
+        fs::write(
+            &brochure_template,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="{{bnasc}}" height="50" fill="blue"/></svg>"#,
+        )
+        .unwrap();
+
+        // Prepare the information page.
+        let information_svg = dir.path().join("information.svg");
+        fs::write(
+            &information_svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="red"/></svg>"#,
+        )
+        .unwrap();
+        export_with_svg2pdf(std::slice::from_ref(&information_svg), system_fontdb()).unwrap();
+
+        let output_dir = dir.path().join("output");
+        let report = run_pipeline(&Pipeline {
+            format: Format::V24,
+            city_ratings,
+            brochure_template,
+            information_page: information_svg.with_extension("pdf"),
+            output_dir: output_dir.clone(),
+            exporter: Exporter::SVG2PDF,
+        })
+        .unwrap();
+
+        for brochure in [
+            "new_zealand-can-christchurch.pdf",
+            "new_zealand-wgn-wellington.pdf",
+        ] {
+            let brochure = output_dir.join(brochure);
+            assert!(brochure.exists(), "{} is missing", brochure.display());
+            assert_eq!(page_count(&brochure).unwrap(), 2);
+        }
+        assert_eq!(report.files_bundled, 2);
+        assert!(output_dir.join("bundles/new_zealand.zip").exists());
+    }
 }
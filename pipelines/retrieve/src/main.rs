@@ -1,17 +1,67 @@
+use bnacore::bundle::{FileType, GroupBy};
+use clap::{Parser, ValueHint};
 use color_eyre::{
     eyre::{eyre, Report},
     Result,
 };
+use spokes::bundle::{run, BundleOptions};
 use std::{
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Output},
 };
 use tracing::info;
 
+/// Retrieve the BNA datasets and bundle them.
+///
+/// Relative default paths are resolved from the root of the repository, making
+/// the pipeline independent of the current working directory.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    /// Version of the City Ratings file
+    #[clap(long, default_value = "v21.15")]
+    city_ratings_version: String,
+    /// City Ratings file [default: assets/city-ratings/city-ratings-<CITY_RATINGS_VERSION>.csv]
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    city_ratings: Option<PathBuf>,
+    /// Output directory [default: pipelines/retrieve/output]
+    #[clap(long, value_hint = ValueHint::DirPath)]
+    output_dir: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Return the City Ratings file.
+    fn city_ratings(&self) -> PathBuf {
+        self.city_ratings.clone().unwrap_or_else(|| {
+            top_dir().join(format!(
+                "assets/city-ratings/city-ratings-{}.csv",
+                self.city_ratings_version
+            ))
+        })
+    }
+
+    /// Return the output directory.
+    fn output_dir(&self) -> PathBuf {
+        self.output_dir
+            .clone()
+            .unwrap_or_else(|| top_dir().join("pipelines/retrieve/output"))
+    }
+}
+
+/// Return the root directory of the repository.
+fn top_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .ancestors()
+        .nth(2)
+        .expect("the pipeline must be located 2 levels below the root of the repository")
+        .to_path_buf()
+}
+
 fn main() -> Result<(), Report> {
     // Setup the application.
     color_eyre::install()?;
+    let cli = Cli::parse();
 
     // Setup logging.
     tracing_subscriber::fmt::fmt()
@@ -19,12 +69,8 @@ fn main() -> Result<(), Report> {
         .init();
 
     // Get the paths.
-    let top_dir = PathBuf::from("../../").canonicalize()?;
-    let asset_dir = top_dir.join("assets");
-    let output_dir = top_dir.join("pipelines/retrieve/output");
-    let city_ratings_15 = asset_dir
-        .join("city_ratings/city_ratings_2021_v15.csv")
-        .canonicalize()?;
+    let output_dir = cli.output_dir();
+    let city_ratings_15 = cli.city_ratings().canonicalize()?;
 
     // Create the output directory.
     info!("📁 Creating the output directory...");
@@ -39,7 +85,10 @@ fn main() -> Result<(), Report> {
         .arg("--bin")
         .arg("retriever")
         .arg("--")
-        .arg("--from-csv")
+        .current_dir(top_dir())
+        .arg("--destination-folder")
+        .arg(&output_dir)
+        .arg("v21")
         .arg(&city_ratings_15)
         .arg("census-block")
         .arg("connected-census-block")
@@ -51,18 +100,14 @@ fn main() -> Result<(), Report> {
 
     // Bundle the datasets.
     info!("📦 Bundling datasets...");
-    let output = Command::new("cargo")
-        .arg("run")
-        .arg("-p")
-        .arg("spokes")
-        .arg("--bin")
-        .arg("bundler")
-        .arg("--")
-        .arg("all")
-        .arg("city")
-        .arg(&output_dir.canonicalize()?)
-        .output()?;
-    process_output(&output)?;
+    let report = run(BundleOptions {
+        input_dir: output_dir.canonicalize()?,
+        group_by: GroupBy::City,
+        filetype: FileType::All,
+        strict: false,
+        manifest: false,
+    })?;
+    info!("{report}");
 
     info!("✅ Done");
     Ok(())
@@ -79,3 +124,30 @@ fn process_output(output: &Output) -> Result<(), Report> {
         String::from_utf8_lossy(&output.stderr),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default paths are absolute, therefore they do not depend on the
+    /// working directory.
+    #[test]
+    fn test_default_paths_ignore_working_directory() {
+        let top_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+
+        let cli = Cli::parse_from(["retrieve"]);
+        assert!(cli.city_ratings().is_absolute());
+        assert_eq!(
+            cli.city_ratings().canonicalize().unwrap(),
+            top_dir
+                .join("assets/city-ratings/city-ratings-v21.15.csv")
+                .canonicalize()
+                .unwrap()
+        );
+        assert!(cli.output_dir().is_absolute());
+        assert!(cli.output_dir().ends_with("pipelines/retrieve/output"));
+
+        let cli = Cli::parse_from(["retrieve", "--output-dir", "out"]);
+        assert_eq!(cli.output_dir(), PathBuf::from("out"));
+    }
+}
@@ -16,7 +16,7 @@
 //!  xsv sample 10 shortcodes-2021-v15.csv > brochure.csv
 //! ```
 //!
-use bnacore::template::{render, Exporter};
+use bnacore::template::{render, Exporter, RenderOptions};
 use color_eyre::{eyre::Report, Result};
 use std::path::PathBuf;
 
@@ -30,14 +30,17 @@ fn main() -> Result<(), Report> {
     let output_dir = example_dir.join("output");
 
     // Render the template.
-    let fields = vec![String::from("co"), String::from("st"), String::from("ci")];
-    render(
-        &brochure_template,
-        &output_dir,
-        Some(Exporter::Inkscape),
-        Some(fields),
-        None,
-    )?;
+    let options = RenderOptions {
+        exporter: Some(Exporter::Inkscape),
+        field_based_name: Some(vec![
+            String::from("co"),
+            String::from("st"),
+            String::from("ci"),
+        ]),
+        ..RenderOptions::default()
+    };
+    let report = render(&brochure_template, &output_dir, &options)?;
+    println!("{report}");
 
     Ok(())
 }
@@ -14,8 +14,11 @@ fn main() -> Result<(), Report> {
         group_by: GroupBy::Country,
         strict: false,
         filetype: FileType::Pdf,
+        with_manifest: false,
     };
 
     // Zip'em.
-    Ok(bundle.zip(true)?)
+    bundle.zip(true)?;
+
+    Ok(())
 }
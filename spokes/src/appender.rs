@@ -1,21 +1,17 @@
-use bnacore::combine::batch_append;
-use clap::{crate_name, ArgAction, Parser, ValueHint};
+//! Standalone version of `spokes append`.
+use clap::{ArgAction, Parser};
 use color_eyre::{eyre::Report, Result};
-use std::path::{Path, PathBuf};
+use spokes::cli::append::{run, AppendArgs};
 
 // CLI options.
 #[derive(Parser, Debug)]
-#[clap(name = crate_name!(), author, about, version)]
+#[clap(name = "appender", author, about, version)]
 pub struct Opts {
     /// Sets the verbosity level
     #[clap(short, long, action = ArgAction::Count)]
     pub verbose: u8,
-    /// Specify the document to append to the other ones
-    #[clap()]
-    pub extra: PathBuf,
-    /// Specify the files to append the extra document to
-    #[clap( value_hint = ValueHint::FilePath)]
-    pub files: Vec<PathBuf>,
+    #[clap(flatten)]
+    pub args: AppendArgs,
 }
 
 fn main() -> Result<(), Report> {
@@ -25,13 +21,5 @@ fn main() -> Result<(), Report> {
     // Setup the CLI.
     let opts: Opts = Opts::parse();
 
-    // Collect all the documents.
-    let f = opts
-        .files
-        .iter()
-        .map(|e| e.as_path())
-        .collect::<Vec<&Path>>();
-
-    // Combine the extra document to them all.
-    Ok(batch_append(&f, &opts.extra)?)
+    run(opts.args)
 }
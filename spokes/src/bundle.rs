@@ -0,0 +1,72 @@
+//! Bundle the files of a directory into archives.
+use bnacore::{
+    bundle::{Bundle, BundleReport, FileType, GroupBy},
+    Error,
+};
+use std::path::PathBuf;
+
+/// Define the options of a bundling operation.
+pub struct BundleOptions {
+    /// Directory containing the files to bundle.
+    pub input_dir: PathBuf,
+    /// How to group the files.
+    pub group_by: GroupBy,
+    /// Which files to look for.
+    pub filetype: FileType,
+    /// Fail if the files do not match the exact name format.
+    pub strict: bool,
+    /// Add a `manifest.json` file describing the content of each archive.
+    pub manifest: bool,
+}
+
+/// Bundle the files as zip archives, in a `bundles` directory created in the
+/// input directory.
+pub fn run(opts: BundleOptions) -> Result<BundleReport, Error> {
+    let bundle = Bundle {
+        input_dir: opts.input_dir,
+        group_by: opts.group_by,
+        strict: opts.strict,
+        filetype: opts.filetype,
+        with_manifest: opts.manifest,
+    };
+    bundle.zip(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_run() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "france-idf-paris.pdf",
+            "united_states-co-boulder.pdf",
+            "united_states-tx-austin.pdf",
+            "notes.pdf",
+        ] {
+            fs::write(dir.path().join(name), b"%PDF-1.7").unwrap();
+        }
+
+        let report = run(BundleOptions {
+            input_dir: dir.path().to_path_buf(),
+            group_by: GroupBy::Country,
+            filetype: FileType::Pdf,
+            strict: false,
+            manifest: false,
+        })
+        .unwrap();
+        assert_eq!(
+            report,
+            BundleReport {
+                groups: 2,
+                files_bundled: 3,
+                files_skipped: 1,
+            }
+        );
+        let bundles = dir.path().join("bundles");
+        assert!(bundles.join("france.zip").exists());
+        assert!(bundles.join("united_states.zip").exists());
+    }
+}
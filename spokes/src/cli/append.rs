@@ -0,0 +1,40 @@
+//! Append a PDF document to other PDF documents.
+use bnacore::combine::{batch_append, is_valid_pdf};
+use clap::{Args, ValueHint};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct AppendArgs {
+    /// Specify the document to append to the other ones
+    #[clap()]
+    pub extra: PathBuf,
+    /// Specify the files to append the extra document to
+    #[clap( value_hint = ValueHint::FilePath)]
+    pub files: Vec<PathBuf>,
+}
+
+/// Append the extra document to all the valid PDF files.
+pub fn run(args: AppendArgs) -> Result<(), Report> {
+    // Ensure the extra document is valid.
+    if !is_valid_pdf(&std::fs::read(&args.extra)?) {
+        return Err(eyre!("invalid PDF document: {}", args.extra.display()));
+    }
+
+    // Collect all the valid documents, and report the invalid ones.
+    let mut f: Vec<&Path> = Vec::new();
+    for file in &args.files {
+        let is_valid = std::fs::read(file).is_ok_and(|bytes| is_valid_pdf(&bytes));
+        if is_valid {
+            f.push(file.as_path());
+        } else {
+            eprintln!("Skipping invalid PDF document: {}", file.display());
+        }
+    }
+
+    // Combine the extra document to them all.
+    Ok(batch_append(&f, &args.extra)?)
+}
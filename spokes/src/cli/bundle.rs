@@ -0,0 +1,98 @@
+//! Bundle the files of a directory into zip archives.
+use crate::bundle::BundleOptions;
+use bnacore::bundle::{FileType, GroupBy};
+use clap::{Args, ValueEnum, ValueHint};
+use color_eyre::{eyre::Report, Result};
+use std::path::PathBuf;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum GroupByArg {
+    City,
+    Country,
+    State,
+    Year,
+}
+
+// These 2 `From` Traits are implemented mainly to make sure that [`GroupBy`]
+// and [`GroupByArg`] stay in sync.
+impl From<GroupBy> for GroupByArg {
+    fn from(group_by: GroupBy) -> Self {
+        match group_by {
+            GroupBy::City => Self::City,
+            GroupBy::Country => Self::Country,
+            GroupBy::State => Self::State,
+            GroupBy::Year => Self::Year,
+        }
+    }
+}
+impl From<GroupByArg> for GroupBy {
+    fn from(group_by_arg: GroupByArg) -> Self {
+        match group_by_arg {
+            GroupByArg::City => Self::City,
+            GroupByArg::Country => Self::Country,
+            GroupByArg::State => Self::State,
+            GroupByArg::Year => Self::Year,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum FiletypeArg {
+    All,
+    Pdf,
+}
+
+// These 2 `From` Traits are implemented mainly to make sure that [`FileType`]
+// and [`FiletypeArg`] stay in sync.
+impl From<FileType> for FiletypeArg {
+    fn from(group_by: FileType) -> Self {
+        match group_by {
+            FileType::All => Self::All,
+            FileType::Pdf => Self::Pdf,
+        }
+    }
+}
+impl From<FiletypeArg> for FileType {
+    fn from(group_by_arg: FiletypeArg) -> Self {
+        match group_by_arg {
+            FiletypeArg::All => Self::All,
+            FiletypeArg::Pdf => Self::Pdf,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BundleArgs {
+    /// Fail if the files do not match the exact name format
+    #[clap(short, long)]
+    pub strict: bool,
+    /// Create an archive containig all the entries
+    #[clap(short, long)]
+    pub all: bool,
+    /// Add a manifest.json file describing the content of each archive
+    #[clap(short, long)]
+    pub manifest: bool,
+    /// Specify which files to look for.
+    #[clap(value_enum)]
+    pub filetype: FiletypeArg,
+    /// Specify how to group the files,
+    #[clap(value_enum)]
+    pub group_by: GroupByArg,
+    /// Specify the directory containing the files to bundle.
+    #[clap(value_parser, value_hint = ValueHint::DirPath)]
+    pub input_dir: PathBuf,
+}
+
+/// Bundle the files and print the report.
+pub fn run(args: BundleArgs) -> Result<(), Report> {
+    let report = crate::bundle::run(BundleOptions {
+        input_dir: args.input_dir,
+        group_by: args.group_by.into(),
+        filetype: args.filetype.into(),
+        strict: args.strict,
+        manifest: args.manifest,
+    })?;
+    println!("{report}");
+
+    Ok(())
+}
@@ -0,0 +1,237 @@
+//! Command line interface of the spokes tools.
+//!
+//! The `spokes` binary exposes each tool as a subcommand. The standalone
+//! binaries (`retriever`, `bundler`, `svggloo`, `shortcodes` and `appender`)
+//! are thin shims parsing the same arguments.
+pub mod append;
+pub mod bundle;
+pub mod render;
+pub mod retrieve;
+pub mod shortcodes;
+
+use bnacore::scorecard::Format;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use color_eyre::{eyre::Report, Result};
+
+/// Describe the ScoreCard formats.
+///
+/// This enum must be in sync with [`bnacore::scorecard::Format`].
+#[derive(Debug, Clone, ValueEnum)]
+pub enum CliFormat {
+    V21,
+    V23,
+    V24,
+}
+
+impl From<Format> for CliFormat {
+    fn from(value: Format) -> Self {
+        match value {
+            Format::V21 => CliFormat::V21,
+            Format::V23 => CliFormat::V23,
+            Format::V24 => CliFormat::V24,
+        }
+    }
+}
+
+impl From<CliFormat> for Format {
+    fn from(value: CliFormat) -> Self {
+        match value {
+            CliFormat::V21 => Format::V21,
+            CliFormat::V23 => Format::V23,
+            CliFormat::V24 => Format::V24,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "spokes", author, about, version)]
+pub struct Cli {
+    /// Sets the verbosity level
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Retrieve the datasets of the cities listed in a city ratings file
+    Retrieve(retrieve::RetrieveArgs),
+    /// Bundle the files of a directory into zip archives
+    Bundle(bundle::BundleArgs),
+    /// Render an SVG template for each record of a data file
+    Render(render::RenderArgs),
+    /// Convert a city ratings file to shortcodes
+    Shortcodes(shortcodes::ShortcodesArgs),
+    /// Append a PDF document to other PDF documents
+    Append(append::AppendArgs),
+}
+
+impl Cli {
+    /// Run the selected subcommand.
+    pub async fn run(self) -> Result<(), Report> {
+        match self.command {
+            Command::Retrieve(args) => retrieve::run(args).await,
+            Command::Bundle(args) => bundle::run(args),
+            Command::Render(args) => render::run(args),
+            Command::Shortcodes(args) => shortcodes::run(args),
+            Command::Append(args) => append::run(args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bnacore::template::Exporter;
+    use clap::CommandFactory;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_retrieve() {
+        let cli = Cli::try_parse_from([
+            "spokes",
+            "retrieve",
+            "--dry-run",
+            "v24",
+            "city-ratings.csv",
+            "overall-scores",
+            "ways",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Retrieve(args) => {
+                assert_eq!(args.from_csv, "city-ratings.csv");
+                assert_eq!(
+                    args.datasets,
+                    vec![
+                        retrieve::CliDataset::OverallScores,
+                        retrieve::CliDataset::Ways
+                    ]
+                );
+                assert_eq!(args.parallel_requests, 32);
+                assert!(args.dry_run);
+            }
+            command => panic!("expected the retrieve command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn test_bundle() {
+        let cli = Cli::try_parse_from(["spokes", "-v", "bundle", "-m", "pdf", "country", "output"])
+            .unwrap();
+        assert_eq!(cli.verbose, 1);
+        match cli.command {
+            Command::Bundle(args) => {
+                assert_eq!(args.filetype, bundle::FiletypeArg::Pdf);
+                assert_eq!(args.group_by, bundle::GroupByArg::Country);
+                assert_eq!(args.input_dir, PathBuf::from("output"));
+                assert!(args.manifest);
+                assert!(!args.strict);
+            }
+            command => panic!("expected the bundle command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let cli = Cli::try_parse_from([
+            "spokes",
+            "render",
+            "--field",
+            "co",
+            "--field",
+            "ci",
+            "-e",
+            "none",
+            "--font-dir",
+            "assets/fonts/Montserrat",
+            "template.svg",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Render(args) => {
+                assert_eq!(args.template, PathBuf::from("template.svg"));
+                assert_eq!(args.output_dir, PathBuf::from("output"));
+                assert_eq!(args.field, Some(vec!["co".to_string(), "ci".to_string()]));
+                assert_eq!(args.exporter.map(Exporter::from), Some(Exporter::None));
+                assert_eq!(args.separator, "-");
+                assert_eq!(
+                    args.font_dir,
+                    Some(vec![PathBuf::from("assets/fonts/Montserrat")])
+                );
+            }
+            command => panic!("expected the render command, got {command:?}"),
+        }
+
+        // The JSON data format can be rendered in parallel.
+        let cli =
+            Cli::try_parse_from(["spokes", "render", "-d", "json", "-j", "4", "template.svg"])
+                .unwrap();
+        match cli.command {
+            Command::Render(args) => {
+                assert_eq!(args.data_format, render::DataFormatArg::Json);
+                assert_eq!(args.jobs, Some(4));
+            }
+            command => panic!("expected the render command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn test_shortcodes() {
+        let cli = Cli::try_parse_from([
+            "spokes",
+            "shortcodes",
+            "--validate",
+            "v24",
+            "city-ratings.csv",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Shortcodes(args) => {
+                assert!(matches!(args.format, CliFormat::V24));
+                assert_eq!(args.city_ratings, PathBuf::from("city-ratings.csv"));
+                assert_eq!(args.output_file, PathBuf::from("brochure.csv"));
+                assert!(args.validate);
+            }
+            command => panic!("expected the shortcodes command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn test_shortcodes_validate_requires_v24() {
+        let cli = Cli::try_parse_from([
+            "spokes",
+            "shortcodes",
+            "--validate",
+            "v23",
+            "city-ratings.csv",
+        ])
+        .unwrap();
+        let Command::Shortcodes(args) = cli.command else {
+            panic!("expected the shortcodes command");
+        };
+        let err = shortcodes::run(args).unwrap_err();
+        assert!(err.to_string().contains("--validate"), "{err}");
+    }
+
+    #[test]
+    fn test_append() {
+        let cli = Cli::try_parse_from(["spokes", "append", "extra.pdf", "a.pdf", "b.pdf"]).unwrap();
+        match cli.command {
+            Command::Append(args) => {
+                assert_eq!(args.extra, PathBuf::from("extra.pdf"));
+                assert_eq!(
+                    args.files,
+                    vec![PathBuf::from("a.pdf"), PathBuf::from("b.pdf")]
+                );
+            }
+            command => panic!("expected the append command, got {command:?}"),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Render an SVG template for each record of a data file, and export the
+//! results to PDF.
+use bnacore::template::{render, system_fontdb, DataFormat, Exporter, RenderOptions};
+use clap::{ArgAction, Args, ValueEnum, ValueHint};
+use color_eyre::{eyre::Report, Result};
+use std::{path::PathBuf, sync::Arc};
+
+/// Define the SVG exporters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum ExporterArg {
+    Inkscape,
+    CairoSVG,
+    SVG2PDF,
+    None,
+}
+
+// These 2 `From` Traits are implemented mainly to make sure that [`Exporter`]
+// and [`ExporterArg`] stay in sync.
+impl From<Exporter> for ExporterArg {
+    fn from(exporter: Exporter) -> Self {
+        match exporter {
+            Exporter::CairoSVG => Self::CairoSVG,
+            Exporter::Inkscape => Self::Inkscape,
+            Exporter::SVG2PDF => Self::SVG2PDF,
+            Exporter::None => Self::None,
+        }
+    }
+}
+impl From<ExporterArg> for Exporter {
+    fn from(exporter_arg: ExporterArg) -> Self {
+        match exporter_arg {
+            ExporterArg::CairoSVG => Self::CairoSVG,
+            ExporterArg::Inkscape => Self::Inkscape,
+            ExporterArg::SVG2PDF => Self::SVG2PDF,
+            ExporterArg::None => Self::None,
+        }
+    }
+}
+
+/// Define the formats of the data file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum DataFormatArg {
+    Csv,
+    Json,
+}
+
+impl From<DataFormat> for DataFormatArg {
+    fn from(data_format: DataFormat) -> Self {
+        match data_format {
+            DataFormat::Csv => Self::Csv,
+            DataFormat::Json => Self::Json,
+        }
+    }
+}
+impl From<DataFormatArg> for DataFormat {
+    fn from(data_format_arg: DataFormatArg) -> Self {
+        match data_format_arg {
+            DataFormatArg::Csv => Self::Csv,
+            DataFormatArg::Json => Self::Json,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Specify the data fields to use to generate the rendered template name
+    // Due to a bug in clap parser, we cannot use a `Option<Vec<String>>` with
+    // multiple values. Therefore we are allowing multiple occurences with one
+    // single value.
+    // Ref: https://github.com/clap-rs/clap/issues/1772
+    // Ref: https://github.com/clap-rs/clap/issues/3066
+    #[clap(long, action = ArgAction::Append, number_of_values = 1)]
+    pub field: Option<Vec<String>>,
+    /// Specify the template
+    #[clap(value_parser, value_hint = ValueHint::FilePath)]
+    pub template: PathBuf,
+    /// Specify the output directory
+    #[clap(value_parser, value_hint = ValueHint::DirPath, default_value = "output")]
+    pub output_dir: PathBuf,
+    /// Specify the separator
+    #[clap(short, long, default_value = "-")]
+    pub separator: String,
+    /// Export the rendered template as PDF, or `none` to only render the SVG
+    #[clap(short, long, value_enum)]
+    pub exporter: Option<ExporterArg>,
+    /// Specify a template for the output file names, e.g. "{{co}}/{{ci}}.svg"
+    #[clap(long)]
+    pub filename: Option<String>,
+    /// Render the records in parallel using the specified number of jobs
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+    /// Load the fonts of this directory for the SVG2PDF exporter, in addition
+    /// to the system fonts
+    #[clap(long, action = ArgAction::Append, number_of_values = 1, value_hint = ValueHint::DirPath)]
+    pub font_dir: Option<Vec<PathBuf>>,
+    /// Specify the format of the data file
+    #[clap(short, long, value_enum, default_value_t = DataFormatArg::Csv)]
+    pub data_format: DataFormatArg,
+}
+
+/// Perform a data-merge operation, and export SVGs to PDFs.
+pub fn run(args: RenderArgs) -> Result<(), Report> {
+    // Add the extra fonts to the system ones.
+    let fontdb = args.font_dir.map(|dirs| {
+        let mut fontdb = (*system_fontdb()).clone();
+        for dir in dirs {
+            fontdb.load_fonts_dir(dir);
+        }
+        Arc::new(fontdb)
+    });
+
+    let options = RenderOptions {
+        exporter: args.exporter.map(Exporter::from),
+        field_based_name: args.field,
+        separator: Some(args.separator),
+        filename_template: args.filename,
+        data_format: args.data_format.into(),
+        jobs: args.jobs,
+        fontdb,
+        ..RenderOptions::default()
+    };
+    let report = render(&args.template, &args.output_dir, &options)?;
+
+    // Report the records which could not be rendered.
+    for (index, e) in &report.failures {
+        eprintln!("Record {index} could not be rendered: {e}");
+    }
+    println!("{report}");
+
+    Ok(())
+}
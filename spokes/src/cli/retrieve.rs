@@ -0,0 +1,404 @@
+//! Retrieve the datasets of the cities listed in a city ratings file.
+use super::CliFormat;
+use bnacore::{
+    scorecard::{
+        scorecard21::ScoreCard21, scorecard23::ScoreCard23, scorecard24::ScoreCard24,
+        ScoreCardVersion, Scorecard, ScorecardCsv, Size,
+    },
+    Dataset, Error,
+};
+use clap::{Args, ValueEnum, ValueHint};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use serde::Serialize;
+use std::{convert::From, fmt, fs, path::PathBuf};
+use trauma::{
+    download::{Download, Status},
+    downloader::DownloaderBuilder,
+};
+use url::Url;
+
+/// Describe all the available city datasets.
+///
+/// This enum must be in sync with [`bnacore::Dataset`].
+#[derive(Debug, PartialEq, Eq, PartialOrd, Clone, Copy, ValueEnum)]
+pub enum CliDataset {
+    CensusBlock,
+    ConnectedCensusBlock,
+    DataDictionary,
+    OverallScores,
+    Ways,
+}
+
+impl From<Dataset> for CliDataset {
+    fn from(dataset: Dataset) -> Self {
+        match dataset {
+            Dataset::CensusBlock => CliDataset::CensusBlock,
+            Dataset::ConnectedCensusBlock => CliDataset::ConnectedCensusBlock,
+            Dataset::DataDictionary => CliDataset::DataDictionary,
+            Dataset::OverallScores => CliDataset::OverallScores,
+            Dataset::Ways => CliDataset::Ways,
+        }
+    }
+}
+
+impl From<&CliDataset> for Dataset {
+    fn from(dataset: &CliDataset) -> Self {
+        match dataset {
+            CliDataset::CensusBlock => Dataset::CensusBlock,
+            CliDataset::ConnectedCensusBlock => Dataset::ConnectedCensusBlock,
+            CliDataset::DataDictionary => Dataset::DataDictionary,
+            CliDataset::OverallScores => Dataset::OverallScores,
+            CliDataset::Ways => Dataset::Ways,
+        }
+    }
+}
+
+impl From<CliDataset> for Dataset {
+    fn from(dataset: CliDataset) -> Self {
+        Dataset::from(&dataset)
+    }
+}
+
+/// Describe the city size categories.
+///
+/// This enum must be in sync with [`bnacore::scorecard::Size`].
+#[derive(Debug, Clone, ValueEnum)]
+pub enum CliSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl From<CliSize> for Size {
+    fn from(value: CliSize) -> Self {
+        match value {
+            CliSize::Small => Size::Small,
+            CliSize::Medium => Size::Medium,
+            CliSize::Large => Size::Large,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RetrieveArgs {
+    /// Number of files to download simultaneously
+    #[clap(short, long, default_value_t = 32)]
+    pub parallel_requests: u16,
+
+    /// Number times to retry a failing download
+    #[clap(short, long, default_value_t = 3)]
+    pub retries: u16,
+
+    /// Destination directory
+    #[clap(short, long,value_parser, value_hint = ValueHint::DirPath, default_value = "output")]
+    pub destination_folder: PathBuf,
+
+    /// ScoreCard format to use
+    #[clap(value_enum)]
+    pub format: CliFormat,
+
+    /// CSV file containing the list of city datasets to download
+    #[clap()]
+    pub from_csv: String,
+
+    /// Dataset(s) to retrieve
+    #[clap(value_enum)]
+    pub datasets: Vec<CliDataset>,
+
+    /// Only retrieve the cities of this size category
+    #[clap(long, value_enum)]
+    pub pop_size: Option<CliSize>,
+
+    /// Only retrieve the cities of this region
+    #[clap(long)]
+    pub region: Option<String>,
+
+    /// Write the list of the planned downloads to a JSON file
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    pub manifest: Option<PathBuf>,
+
+    /// Only plan the downloads, without downloading anything
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// Describe a download planned by the retriever.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedDownload {
+    /// Full name of the city.
+    pub city: String,
+    /// Name of the dataset.
+    pub dataset: String,
+    /// URL of the dataset.
+    pub url: Url,
+    /// Name of the downloaded file.
+    pub filename: String,
+}
+
+impl PlannedDownload {
+    /// Create the download matching this plan.
+    pub fn download(&self) -> Download {
+        Download::new(&self.url, &self.filename)
+    }
+}
+
+/// Describe a download which did not succeed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadFailure {
+    /// Full name of the city.
+    pub city: String,
+    /// Name of the dataset.
+    pub dataset: String,
+    /// URL of the dataset.
+    pub url: Url,
+    /// Final status of the download.
+    pub status: Status,
+}
+
+impl fmt::Display for DownloadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match &self.status {
+            Status::Fail(reason) => reason.as_str(),
+            Status::NotStarted => "not started",
+            Status::Skipped(reason) => reason.as_str(),
+            Status::Success => "success",
+        };
+        write!(
+            f,
+            "{} ({}): {} [{}]",
+            self.city, self.dataset, reason, self.url
+        )
+    }
+}
+
+/// Build the report of the failed downloads.
+///
+/// The results are matched with the planned downloads using their filename.
+/// The successful and skipped downloads are not reported.
+fn failure_report(
+    planned: &[PlannedDownload],
+    results: &[(Download, Status)],
+) -> Vec<DownloadFailure> {
+    results
+        .iter()
+        .filter(|(_, status)| matches!(status, Status::Fail(_) | Status::NotStarted))
+        .map(|(download, status)| {
+            let plan = planned.iter().find(|p| p.filename == download.filename);
+            DownloadFailure {
+                city: plan.map_or_else(|| download.filename.clone(), |p| p.city.clone()),
+                dataset: plan.map_or_else(String::new, |p| p.dataset.clone()),
+                url: download.url.clone(),
+                status: status.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Plan the downloads of the datasets for each city.
+fn plan_downloads<I>(scorecards: I, datasets: &[Dataset]) -> Result<Vec<PlannedDownload>, Report>
+where
+    I: IntoIterator<Item = Result<ScoreCardVersion, Error>>,
+{
+    let mut planned: Vec<PlannedDownload> = Vec::new();
+    for city in scorecards {
+        let city = city?;
+        // Prepare the dataset downloads for this city.
+        for (ds, url) in city.dataset_urls(datasets)? {
+            let filename = format!("{}-{}.{}", &city.full_name(), &ds, &ds.extension());
+            planned.push(PlannedDownload {
+                city: city.full_name(),
+                dataset: ds.to_string(),
+                url,
+                filename: filename.replace(' ', "_"),
+            });
+        }
+    }
+    Ok(planned)
+}
+
+/// Download the requested datasets of the selected cities.
+pub async fn run(args: RetrieveArgs) -> Result<(), Report> {
+    // Stream the scorecards from the CSV file.
+    let scorecards: Box<dyn Iterator<Item = Result<ScoreCardVersion, Error>>> = match args.format {
+        CliFormat::V21 => Box::new(
+            ScoreCard21::from_csv_iter(args.from_csv)?.map(|e| e.map(ScoreCardVersion::V21)),
+        ),
+        CliFormat::V23 => Box::new(
+            ScoreCard23::from_csv_iter(args.from_csv)?.map(|e| e.map(ScoreCardVersion::V23)),
+        ),
+        CliFormat::V24 => Box::new(
+            ScoreCard24::from_csv_iter(args.from_csv)?.map(|e| e.map(ScoreCardVersion::V24)),
+        ),
+    };
+
+    // Keep only the requested cities, letting the parsing errors through.
+    let pop_size = args.pop_size.map(Size::from);
+    let region = args.region.as_deref();
+    let scorecards = scorecards.filter(|s| {
+        s.as_ref()
+            .map_or(true, |s| s.matches(pop_size.as_ref(), region))
+    });
+
+    // Plan the downloads for each city.
+    let datasets = args
+        .datasets
+        .iter()
+        .map(Dataset::from)
+        .collect::<Vec<Dataset>>();
+    let planned = plan_downloads(scorecards, &datasets)?;
+
+    // Write the manifest.
+    if let Some(manifest) = &args.manifest {
+        fs::write(manifest, serde_json::to_string_pretty(&planned)?)?;
+    }
+
+    // Stop before downloading anything.
+    if args.dry_run {
+        if args.manifest.is_none() {
+            println!("{}", serde_json::to_string_pretty(&planned)?);
+        }
+        return Ok(());
+    }
+
+    // Ensure the output folder exists.
+    if !args.destination_folder.exists() {
+        fs::create_dir_all(&args.destination_folder)?;
+    }
+
+    // Prepare the downloader.
+    let downloader = DownloaderBuilder::new()
+        .directory(args.destination_folder)
+        .build();
+
+    // Prepare the downloads.
+    let downloads = planned
+        .iter()
+        .map(PlannedDownload::download)
+        .collect::<Vec<Download>>();
+
+    // Start the download operations.
+    let dl_result = downloader.download(&downloads).await;
+
+    // Display information about the failures.
+    let results = dl_result
+        .iter()
+        .map(|s| (s.download().clone(), s.status().clone()))
+        .collect::<Vec<(Download, Status)>>();
+    let failures = failure_report(&planned, &results);
+    if !failures.is_empty() {
+        eprintln!("Failed downloads:");
+        for failure in &failures {
+            eprintln!("  - {failure}");
+        }
+        return Err(eyre!(
+            "{} download(s) failed out of {}",
+            failures.len(),
+            downloads.len()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_downloads() {
+        let raw_json = r#"
+          {
+            "city": "Christchurch",
+            "state": "CAN",
+            "state_full": "Canterbury",
+            "country": "New Zealand",
+            "region": "New Zealand",
+            "year": 2023,
+            "census_population": 389300,
+            "census_latitude": -43.532,
+            "census_longitude": 172.6306,
+            "bna_id": "ae2250a5-9c90-4132-929d-63640c23d1c5",
+            "bna_uuid": "9ac2465b-04f7-48a0-adc6-92502243b6e3",
+            "bna_rounded_score": 53,
+            "creation_date": "2023-04-17T16:31:00Z",
+            "filename": "_Christchurch_v23.1.csv"
+          }"#;
+        let scorecards = [ScoreCardVersion::V24(
+            serde_json::from_str::<ScoreCard24>(raw_json).unwrap(),
+        )];
+        let datasets = [Dataset::OverallScores, Dataset::Ways];
+        let planned = plan_downloads(scorecards.iter().cloned().map(Ok), &datasets).unwrap();
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[1].city, "New Zealand-Canterbury-Christchurch");
+        assert_eq!(planned[1].dataset, Dataset::Ways.to_string());
+        assert_eq!(
+            planned[1].filename,
+            format!("New_Zealand-Canterbury-Christchurch-{}.zip", Dataset::Ways)
+        );
+
+        // The manifest rows match the downloads.
+        for (plan, (ds, url)) in planned
+            .iter()
+            .zip(scorecards[0].dataset_urls(&datasets).unwrap())
+        {
+            let download = plan.download();
+            assert_eq!(download.url, url);
+            assert_eq!(download.filename, plan.filename);
+            assert_eq!(plan.dataset, ds.to_string());
+        }
+        let manifest = serde_json::to_value(&planned).unwrap();
+        assert_eq!(manifest[0]["url"], planned[0].url.as_str());
+    }
+
+    #[test]
+    fn test_plan_downloads_invalid_record() {
+        let scorecards = [Err(Error::InvalidArgument("bad record".to_string()))];
+        assert!(plan_downloads(scorecards, &[Dataset::Ways]).is_err());
+    }
+
+    #[test]
+    fn test_failure_report() {
+        let plan = |city: &str, dataset: &str| PlannedDownload {
+            city: city.to_string(),
+            dataset: dataset.to_string(),
+            url: Url::parse(&format!("https://example.com/{city}/{dataset}")).unwrap(),
+            filename: format!("{city}-{dataset}"),
+        };
+        let planned = vec![
+            plan("Austin", "ways"),
+            plan("Boulder", "ways"),
+            plan("Denver", "ways"),
+            plan("Pueblo", "ways"),
+        ];
+        let results = vec![
+            (planned[0].download(), Status::Success),
+            (
+                planned[1].download(),
+                Status::Fail("404 Not Found".to_string()),
+            ),
+            (
+                planned[2].download(),
+                Status::Skipped("already exists".to_string()),
+            ),
+            (planned[3].download(), Status::NotStarted),
+        ];
+
+        let failures = failure_report(&planned, &results);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].city, "Boulder");
+        assert_eq!(failures[0].dataset, "ways");
+        assert_eq!(
+            failures[0].status,
+            Status::Fail("404 Not Found".to_string())
+        );
+        assert_eq!(
+            failures[0].to_string(),
+            "Boulder (ways): 404 Not Found [https://example.com/Boulder/ways]"
+        );
+        assert_eq!(failures[1].city, "Pueblo");
+        assert_eq!(failures[1].status, Status::NotStarted);
+    }
+}
@@ -0,0 +1,80 @@
+//! Convert a city ratings file to shortcodes.
+use super::CliFormat;
+use bnacore::scorecard::{
+    scorecard24::ScoreCard24, shortscorecard::ShortScoreCard, Format, ScoreCardVersion, Scorecard,
+    ScorecardCsv,
+};
+use clap::{Args, ValueHint};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use std::{fs, path::PathBuf};
+
+#[derive(Args, Debug)]
+pub struct ShortcodesArgs {
+    /// ScoreCard format to use
+    #[clap(value_enum)]
+    pub format: CliFormat,
+    /// Specify the template
+    #[clap(value_parser, value_hint = ValueHint::FilePath)]
+    pub city_ratings: PathBuf,
+    /// Specify the output directory
+    #[clap(value_parser, value_hint = ValueHint::FilePath, default_value = "brochure.csv")]
+    pub output_file: PathBuf,
+    /// Validate the scores and report the invalid rows (v24 format only)
+    #[clap(long)]
+    pub validate: bool,
+}
+
+/// Convert the city ratings to shortcodes and write them to the output file.
+pub fn run(args: ShortcodesArgs) -> Result<(), Report> {
+    // Only the v24 scorecards can be validated.
+    if args.validate && !matches!(args.format, CliFormat::V24) {
+        return Err(eyre!("--validate is only supported with the v24 format"));
+    }
+
+    // Convert to shortcode, one record at a time.
+    let short_scorecards: Vec<ShortScoreCard> = match args.format {
+        CliFormat::V21 => ShortScoreCard::from_city_ratings(&Format::V21, args.city_ratings)?,
+        CliFormat::V23 => ShortScoreCard::from_city_ratings(&Format::V23, args.city_ratings)?,
+        CliFormat::V24 => {
+            // The validation reports all the invalid rows at once, therefore it
+            // needs all of them.
+            let scorecards = ScoreCard24::from_csv(args.city_ratings)?;
+            if args.validate {
+                validate(&scorecards)?;
+            }
+            scorecards
+                .into_iter()
+                .map(|e| ShortScoreCard::from(&ScoreCardVersion::V24(e)))
+                .collect()
+        }
+    };
+    if let Some(dir) = &args.output_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(ShortScoreCard::write_csv(
+        args.output_file,
+        &short_scorecards,
+    )?)
+}
+
+/// Report the invalid scorecards.
+fn validate(scorecards: &[ScoreCard24]) -> Result<(), Report> {
+    let mut invalid_rows: usize = 0;
+    for (i, scorecard) in scorecards.iter().enumerate() {
+        if let Err(problems) = scorecard.validate() {
+            invalid_rows += 1;
+            // Account for the header row, and for the rows starting at 1.
+            eprintln!("Row {} ({}):", i + 2, scorecard.full_name());
+            for problem in problems {
+                eprintln!("  - {problem}");
+            }
+        }
+    }
+    if invalid_rows > 0 {
+        return Err(eyre!("{invalid_rows} invalid row(s) found"));
+    }
+    Ok(())
+}
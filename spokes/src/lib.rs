@@ -1 +1,6 @@
-
+//! Library API of the spokes tools.
+//!
+//! It lets the other tools of the workspace run the spokes in-process, instead
+//! of spawning the binaries.
+pub mod bundle;
+pub mod cli;
@@ -0,0 +1,14 @@
+use clap::Parser;
+use color_eyre::{eyre::Report, Result};
+use spokes::cli::Cli;
+
+#[tokio::main]
+async fn main() -> Result<(), Report> {
+    // Setup the application.
+    color_eyre::install()?;
+
+    // Setup the CLI.
+    let cli = Cli::parse();
+
+    cli.run().await
+}